@@ -0,0 +1,52 @@
+// Measures how many 8-oscillator unison voices one core can render in real time.
+// Run with `cargo bench --no-default-features --bench voices`.
+use pulsar::synth::voice_pool::MAX_VOICES;
+use pulsar::synth::CHANNELS;
+use pulsar::{Quality, Synth, WaveForm, ADSR};
+use std::hint::black_box;
+use std::time::Instant;
+
+const SAMPLE_RATE: f32 = 48000.0;
+const BLOCK_SIZE: usize = 256;
+const SECONDS: usize = 2;
+
+fn bench(waveform: WaveForm, quality: Quality) {
+    let mut synth = Synth::new(SAMPLE_RATE, ADSR::new(5, 100, 0.8, 100));
+    synth.num_oscillators = 8;
+    synth.set_max_polyphony(MAX_VOICES);
+    synth.set_waveform(waveform);
+    synth.set_quality(quality);
+    synth.set_detune(0.5);
+
+    for note in 0..MAX_VOICES {
+        synth.note_on(24 + note as u8, 100);
+    }
+
+    let mut block = vec![0.0; BLOCK_SIZE * CHANNELS];
+    let blocks = SAMPLE_RATE as usize * SECONDS / BLOCK_SIZE;
+
+    let start = Instant::now();
+    for _ in 0..blocks {
+        synth.process(black_box(&mut block));
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let rendered = (blocks * BLOCK_SIZE) as f64 / SAMPLE_RATE as f64;
+    let realtime = rendered / elapsed;
+    println!(
+        "{:<12} {:<12} {:>6.1}x real time  ~{:>5.0} voices per core",
+        format!("{:?}", waveform),
+        format!("{:?}", quality),
+        realtime,
+        realtime * MAX_VOICES as f64,
+    );
+}
+
+fn main() {
+    println!("{} voices x 8 oscillators at {} Hz", MAX_VOICES, SAMPLE_RATE);
+    for waveform in [WaveForm::Sine, WaveForm::Saw, WaveForm::Square, WaveForm::Pulse, WaveForm::Triangle, WaveForm::WhiteNoise] {
+        for quality in [Quality::Naive, Quality::BandLimited] {
+            bench(waveform, quality);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+// Debug-build check that the real-time render path never touches the heap.
+//
+// Code on the audio path opens a `RealtimeScope`. When `AllocGuard` is the
+// global allocator (tests install it with `#[global_allocator]`), any heap
+// allocation made while a scope is open on that thread is counted, and the
+// scope panics when it closes. Release builds compile all of this away.
+
+thread_local! {
+    static SCOPE_DEPTH: Cell<u32> = const { Cell::new(0) };
+    static VIOLATIONS: Cell<u32> = const { Cell::new(0) };
+}
+
+pub struct AllocGuard;
+
+fn record_allocation() {
+    if cfg!(debug_assertions) {
+        // try_with: the allocator can run while thread-locals are being torn down
+        let in_scope = SCOPE_DEPTH.try_with(|depth| depth.get() > 0).unwrap_or(false);
+        if in_scope {
+            let _ = VIOLATIONS.try_with(|count| count.set(count.get() + 1));
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for AllocGuard {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record_allocation();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        record_allocation();
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[must_use = "the scope only guards code that runs while it is alive"]
+pub struct RealtimeScope {
+    #[cfg(debug_assertions)]
+    violations_before: u32,
+}
+
+impl RealtimeScope {
+    pub fn enter() -> Self {
+        #[cfg(debug_assertions)]
+        {
+            SCOPE_DEPTH.with(|depth| depth.set(depth.get() + 1));
+            RealtimeScope {
+                violations_before: VIOLATIONS.with(Cell::get),
+            }
+        }
+        #[cfg(not(debug_assertions))]
+        RealtimeScope {}
+    }
+}
+
+impl Drop for RealtimeScope {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            SCOPE_DEPTH.with(|depth| depth.set(depth.get() - 1));
+            let violations = VIOLATIONS.with(Cell::get) - self.violations_before;
+            if violations > 0 && !std::thread::panicking() {
+                panic!("{} heap allocation(s) on the real-time audio path", violations);
+            }
+        }
+    }
+}
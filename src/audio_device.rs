@@ -0,0 +1,103 @@
+use crate::config::Config;
+use crate::error::PulsarError;
+use pulsar::synth::CHANNELS;
+use rodio::cpal::{
+    self,
+    traits::{DeviceTrait, HostTrait},
+    SampleRate, SupportedStreamConfig,
+};
+use rodio::{Device, OutputStream, OutputStreamHandle};
+
+pub struct AudioOutput {
+    pub stream:         OutputStream,
+    pub handle:         OutputStreamHandle,
+    pub device_name:    String,
+    pub sample_rate:    u32,
+}
+
+pub fn print_devices() -> Result<(), PulsarError> {
+    let host = cpal::default_host();
+    let default_name = host.default_output_device().and_then(|device| device.name().ok());
+    let devices = host.output_devices().map_err(|e| host_error(&host, e))?;
+
+    for (index, device) in devices.enumerate() {
+        let name = device.name().unwrap_or_else(|_| "<unnamed>".to_string());
+        let marker = if Some(&name) == default_name.as_ref() { " (default)" } else { "" };
+        println!("{}: {}{}", index, name, marker);
+
+        if let Ok(ranges) = device.supported_output_configs() {
+            for range in ranges {
+                println!(
+                    "     {} ch, {}-{} Hz, {:?}",
+                    range.channels(),
+                    range.min_sample_rate().0,
+                    range.max_sample_rate().0,
+                    range.sample_format(),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+// Opens the configured device. The returned sample rate is what the device
+// actually runs at, so the synth can be built to match it.
+pub fn open(config: &Config) -> Result<AudioOutput, PulsarError> {
+    let host = cpal::default_host();
+    let device = match &config.device {
+        Some(name) => find_device(&host, name)?,
+        None => host.default_output_device().ok_or(PulsarError::NoOutputDevice)?,
+    };
+    let device_name = device.name().unwrap_or_else(|_| "<unnamed>".to_string());
+
+    let stream_config = match config.sample_rate {
+        Some(sample_rate) => config_for_rate(&device, sample_rate).ok_or_else(|| {
+            PulsarError::UnsupportedSampleRate { device: device_name.clone(), sample_rate }
+        })?,
+        None => device.default_output_config().map_err(|e| PulsarError::AudioDevice {
+            device: device_name.clone(),
+            message: e.to_string(),
+        })?,
+    };
+    let sample_rate = stream_config.sample_rate().0;
+
+    let (stream, handle) = OutputStream::try_from_device_config(&device, stream_config)?;
+
+    Ok(AudioOutput { stream, handle, device_name, sample_rate })
+}
+
+// Accepts either the index shown by --list-devices or (part of) the device name
+fn find_device(host: &cpal::Host, name: &str) -> Result<Device, PulsarError> {
+    let devices: Vec<Device> = host.output_devices()
+        .map_err(|e| host_error(host, e))?
+        .collect();
+
+    let found = match name.parse::<usize>() {
+        Ok(index) => devices.into_iter().nth(index),
+        Err(_) => {
+            let lowercase = name.to_lowercase();
+            devices.into_iter()
+                .find(|device| device.name().is_ok_and(|device_name| device_name.to_lowercase().contains(&lowercase)))
+        }
+    };
+    found.ok_or_else(|| PulsarError::DeviceNotFound(name.to_string()))
+}
+
+fn host_error(host: &cpal::Host, error: cpal::DevicesError) -> PulsarError {
+    PulsarError::AudioDevice {
+        device: format!("host {:?}", host.id()),
+        message: error.to_string(),
+    }
+}
+
+// Prefers a stereo configuration, falling back to any channel count that supports the rate
+fn config_for_rate(device: &Device, rate: u32) -> Option<SupportedStreamConfig> {
+    let ranges: Vec<_> = device.supported_output_configs().ok()?
+        .filter(|range| range.min_sample_rate().0 <= rate && rate <= range.max_sample_rate().0)
+        .collect();
+
+    ranges.iter()
+        .find(|range| range.channels() as usize == CHANNELS)
+        .or_else(|| ranges.first())
+        .map(|range| range.with_sample_rate(SampleRate(rate)))
+}
@@ -0,0 +1,217 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use pulsar::midi::{MidiMessage, MidiRecorder};
+use pulsar::synth::{Command, TapReader, CHANNELS};
+use crate::error::PulsarError;
+
+// How often the writer thread empties the tap
+const WRITE_INTERVAL: Duration = Duration::from_millis(20);
+
+type WavFile = hound::WavWriter<BufWriter<File>>;
+
+// Records what is played live, written out as a Standard MIDI File when it stops
+pub struct MidiCapture {
+    started:    Instant,
+    recorder:   MidiRecorder,
+    path:       PathBuf,
+}
+
+impl MidiCapture {
+    // Claims the file straight away so a second capture can't pick the same name
+    pub fn start(dir: &str) -> Result<Self, PulsarError> {
+        let (path, _) = create_unique(dir, "mid")
+            .map_err(|source| PulsarError::Io { path: dir.to_string(), source })?;
+        Ok(MidiCapture {
+            started: Instant::now(),
+            recorder: MidiRecorder::new(),
+            path,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn elapsed(&self) -> f64 {
+        self.started.elapsed().as_secs_f64()
+    }
+
+    pub fn record_command(&mut self, command: &Command) {
+        let time = self.elapsed();
+        self.recorder.record_command(time, command);
+    }
+
+    pub fn record_message(&mut self, message: MidiMessage) {
+        let time = self.elapsed();
+        self.recorder.record(time, message);
+    }
+
+    // Writes the file, releasing any notes still held at this moment
+    pub fn finish(self) -> Result<PathBuf, PulsarError> {
+        let time = self.elapsed();
+        self.recorder.finish(time).save(&self.path)
+            .map_err(|source| PulsarError::Io { path: self.path.display().to_string(), source })?;
+        Ok(self.path)
+    }
+}
+
+// Records the audio output to WAV files. The audio thread copies each rendered
+// block into the tap; a writer thread drains it to disk, so the file system can
+// never hold up the audio.
+pub struct WavCapture {
+    reader:         Option<TapReader>,
+    sample_rate:    u32,
+    recording:      Option<WavRecording>,
+}
+
+struct WavRecording {
+    path:       PathBuf,
+    frames:     Arc<AtomicU64>,
+    done:       Arc<AtomicBool>,
+    writer:     JoinHandle<(TapReader, Result<(), hound::Error>)>,
+}
+
+impl WavCapture {
+    pub fn new(reader: TapReader, sample_rate: u32) -> Self {
+        WavCapture {
+            reader: Some(reader),
+            sample_rate,
+            recording: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // Length of the audio written so far
+    pub fn seconds(&self) -> f64 {
+        self.recording.as_ref().map_or(0.0, |recording| {
+            recording.frames.load(Ordering::Relaxed) as f64 / self.sample_rate as f64
+        })
+    }
+
+    pub fn start(&mut self, dir: &str) -> Result<&Path, PulsarError> {
+        let (path, file) = create_unique(dir, "wav")
+            .map_err(|source| PulsarError::Wav { path: dir.to_string(), source: hound::Error::IoError(source) })?;
+        let spec = hound::WavSpec {
+            channels: CHANNELS as u16,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let file = hound::WavWriter::new(BufWriter::new(file), spec)
+            .map_err(|source| PulsarError::Wav { path: path.display().to_string(), source })?;
+        let Some(mut reader) = self.reader.take() else {
+            return Err(PulsarError::AudioThread("the recording tap is in use".to_string()));
+        };
+
+        let frames = Arc::new(AtomicU64::new(0));
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (frames, done) = (Arc::clone(&frames), Arc::clone(&done));
+            thread::Builder::new()
+                .name("wav_writer".to_string())
+                .spawn(move || {
+                    reader.start();
+                    let result = write_tap(&mut reader, file, &frames, &done);
+                    reader.stop();
+                    (reader, result)
+                })
+                .map_err(|e| PulsarError::AudioThread(e.to_string()))?
+        };
+
+        let recording = self.recording.insert(WavRecording { path, frames, done, writer });
+        Ok(&recording.path)
+    }
+
+    // Finishes the file once the writer has caught up; None if nothing was recording
+    pub fn stop(&mut self) -> Option<Result<(PathBuf, u64), PulsarError>> {
+        let recording = self.recording.take()?;
+        recording.done.store(true, Ordering::Release);
+        let Ok((reader, result)) = recording.writer.join() else {
+            return Some(Err(PulsarError::AudioThread("the WAV writer panicked".to_string())));
+        };
+        let dropped = reader.dropped();
+        self.reader = Some(reader);
+
+        let path = recording.path;
+        Some(match result {
+            Ok(()) => Ok((path, dropped)),
+            Err(source) => Err(PulsarError::Wav { path: path.display().to_string(), source }),
+        })
+    }
+}
+
+fn write_tap(reader: &mut TapReader, mut file: WavFile, frames: &AtomicU64, done: &AtomicBool) -> Result<(), hound::Error> {
+    let mut samples = 0u64;
+    loop {
+        // Checked before draining so the last blocks are written after a stop
+        let finishing = done.load(Ordering::Acquire);
+        if finishing {
+            reader.stop();
+        }
+        while let Some(sample) = reader.read() {
+            file.write_sample(sample)?;
+            samples += 1;
+        }
+        frames.store(samples / CHANNELS as u64, Ordering::Relaxed);
+        if finishing {
+            // A block the audio thread was still copying in when the tap stopped
+            // may be cut short; pad it out to a whole frame
+            while !samples.is_multiple_of(CHANNELS as u64) {
+                file.write_sample(0.0f32)?;
+                samples += 1;
+            }
+            return file.finalize();
+        }
+        thread::sleep(WRITE_INTERVAL);
+    }
+}
+
+// Creates a new `pulsar-<timestamp>.<extension>` in `dir`. Captures started within
+// the same second get `-2`, `-3` and so on rather than overwriting each other.
+fn create_unique(dir: &str, extension: &str) -> io::Result<(PathBuf, File)> {
+    let stem = format!("pulsar-{}", timestamp());
+    let mut attempt = 1;
+    loop {
+        let name = if attempt == 1 {
+            format!("{}.{}", stem, extension)
+        } else {
+            format!("{}-{}.{}", stem, attempt, extension)
+        };
+        let path = Path::new(dir).join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+// UTC date and time as `yyyymmdd-hhmmss`, for file names that sort by when they were made
+pub fn timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()) as i64;
+    let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's days_from_civil, inverted)
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year, month, day, time / 3600, time % 3600 / 60, time % 60
+    )
+}
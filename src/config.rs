@@ -0,0 +1,145 @@
+use std::fs;
+use crate::error::PulsarError;
+use crate::keyboard::InputKind;
+use pulsar::input::key_mapping::Layout;
+
+pub const DEFAULT_RENDER_BLOCK: usize = 256;
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+pub const USAGE: &str = "\
+usage: pulsar [options]
+       pulsar play <song.mid|events.txt> [--config PATH] [--device NAME] [--sample-rate HZ] [--render-block FRAMES]
+       pulsar render <song.mid|events.txt> <out.wav> [--format 16|24|32] [--sample-rate HZ]
+
+options:
+  --config PATH          read settings from a config file (command-line flags win)
+  --list-devices         list audio output devices and exit
+  --device NAME          output device, by index or (part of) its name
+  --sample-rate HZ       request a sample rate from the device
+  --render-block FRAMES  frames the synth renders at a time (the device buffer is left to the driver)
+  --input BACKEND        keyboard input: auto, terminal or device_query (default auto)
+  --layout NAME          keyboard layout: qwerty, azerty, qwertz or dvorak (default qwerty)
+  --keymap PATH          load `key = note` mappings from a file, on top of the layout
+  --midi SOURCE          MIDI input: `alsa` for a virtual sequencer port, or a raw MIDI device path
+  --record-dir DIR       where recordings are saved (default: the current directory)
+
+config file keys (one `key = value` per line, `#` starts a comment):
+  device, sample_rate, render_block, input, layout, keymap, midi, record_dir
+
+while playing:
+  Space next waveform, Tab toggle band-limiting, Left/Right octave down/up,
+  Down/Up transpose by a semitone, F5 start/stop recording to a .mid file,
+  F6 start/stop recording audio to a .wav file, Esc quit";
+
+// Settings for the interactive app, read from a config file and/or the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub device:         Option<String>,
+    pub sample_rate:    Option<u32>,
+    pub render_block:   usize,
+    pub input:          InputKind,
+    pub layout:         Layout,
+    pub keymap:         Option<String>,
+    pub midi:           Option<String>,
+    pub record_dir:     String,
+    pub list_devices:   bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            device:         None,
+            sample_rate:    None,
+            render_block:   DEFAULT_RENDER_BLOCK,
+            input:          InputKind::Auto,
+            layout:         Layout::Qwerty,
+            keymap:         None,
+            midi:           None,
+            record_dir:     ".".to_string(),
+            list_devices:   false,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(args: &[String]) -> Result<Self, PulsarError> {
+        let mut config = Config::default();
+
+        // The config file is read first so command-line flags override it
+        if let Some(index) = args.iter().position(|arg| arg == "--config") {
+            let path = args.get(index + 1).ok_or_else(|| PulsarError::Usage("--config needs a path".to_string()))?;
+            config.read_file(path)?;
+        }
+        config.apply_args(args).map_err(PulsarError::Usage)?;
+        Ok(config)
+    }
+
+    fn read_file(&mut self, path: &str) -> Result<(), PulsarError> {
+        let text = fs::read_to_string(path)
+            .map_err(|source| PulsarError::Io { path: path.to_string(), source })?;
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| PulsarError::Config(format!("{}:{}: expected `key = value`", path, index + 1)))?;
+            self.set(key.trim(), value.trim())
+                .map_err(|e| PulsarError::Config(format!("{}:{}: {}", path, index + 1, e)))?;
+        }
+        Ok(())
+    }
+
+    fn apply_args(&mut self, args: &[String]) -> Result<(), String> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--config" => {
+                    value()?;
+                }
+                "--device" => self.set("device", value()?)?,
+                "--sample-rate" => self.set("sample_rate", value()?)?,
+                "--render-block" => self.set("render_block", value()?)?,
+                "--input" => self.set("input", value()?)?,
+                "--layout" => self.set("layout", value()?)?,
+                "--keymap" => self.set("keymap", value()?)?,
+                "--midi" => self.set("midi", value()?)?,
+                "--record-dir" => self.set("record_dir", value()?)?,
+                "--list-devices" => self.list_devices = true,
+                _ => return Err(format!("unknown option `{}`\n\n{}", arg, USAGE)),
+            }
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "device" => self.device = Some(value.to_string()),
+            "sample_rate" => self.sample_rate = Some(parse_positive(key, value)? as u32),
+            "render_block" => self.render_block = parse_positive(key, value)?,
+            "input" => {
+                self.input = InputKind::parse(value)
+                    .ok_or_else(|| format!("input must be auto, terminal or device_query, got `{}`", value))?
+            }
+            "layout" => {
+                self.layout = Layout::from_name(value)
+                    .ok_or_else(|| format!("layout must be qwerty, azerty, qwertz or dvorak, got `{}`", value))?
+            }
+            "keymap" => self.keymap = Some(value.to_string()),
+            "midi" => self.midi = Some(value.to_string()),
+            "record_dir" => self.record_dir = value.to_string(),
+            _ => return Err(format!("unknown setting `{}`", key)),
+        }
+        Ok(())
+    }
+}
+
+fn parse_positive(key: &str, value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(number) if number > 0 && number <= u32::MAX as usize => Ok(number),
+        _ => Err(format!("{} must be a positive whole number, got `{}`", key, value)),
+    }
+}
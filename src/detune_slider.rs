@@ -1,4 +1,4 @@
-use std::io::{stdout, Write};
+use std::io::{self, stdout, Write};
 
 pub struct Slider {
     pub min:        f32,
@@ -22,7 +22,7 @@ impl Slider {
         self.value = self.min + ratio * (self.max - self.min);
     }
 
-    pub fn draw(&self, label: &str) {
+    pub fn draw(&self, label: &str) -> io::Result<()> {
         print!("\r {}: ", label);
 
         let filled_length = ((self.value - self.min) / (self.max - self.min) * self.width as f32) as usize;
@@ -36,7 +36,7 @@ impl Slider {
         }
         print!("] {:2}", self.value);
 
-        stdout().flush().unwrap();
+        stdout().flush()
     }
 
     pub fn get_value(&self) -> f32 {
@@ -0,0 +1,105 @@
+use std::{fmt, io};
+use pulsar::input::key_mapping::KeyMapIssue;
+use pulsar::midi::SmfError;
+use pulsar::render::ParseEventError;
+
+// Everything that can stop the app from starting or running, reported as a
+// single line on stderr instead of a panic
+#[derive(Debug)]
+pub enum PulsarError {
+    Usage(String),
+    Config(String),
+    NoOutputDevice,
+    DeviceNotFound(String),
+    UnsupportedSampleRate { device: String, sample_rate: u32 },
+    AudioDevice { device: String, message: String },
+    Stream(rodio::StreamError),
+    Sink(rodio::PlayError),
+    AudioThread(String),
+    Terminal(io::Error),
+    Input(String),
+    Midi(String),
+    KeyMap { path: String, issues: Vec<KeyMapIssue> },
+    Io { path: String, source: io::Error },
+    Events { path: String, source: ParseEventError },
+    MidiFile { path: String, source: SmfError },
+    Wav { path: String, source: hound::Error },
+}
+
+impl PulsarError {
+    // Exit status: 2 for bad invocations, 1 for everything else
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            PulsarError::Usage(_) | PulsarError::Config(_) | PulsarError::KeyMap { .. } => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for PulsarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PulsarError::Usage(message) => write!(f, "{}", message),
+            PulsarError::Config(message) => write!(f, "{}", message),
+            PulsarError::NoOutputDevice => write!(f, "no audio output device found"),
+            PulsarError::DeviceNotFound(name) => {
+                write!(f, "no output device matching `{}` (see --list-devices)", name)
+            }
+            PulsarError::UnsupportedSampleRate { device, sample_rate } => {
+                write!(f, "{} does not support {} Hz (see --list-devices)", device, sample_rate)
+            }
+            PulsarError::AudioDevice { device, message } => write!(f, "audio device {}: {}", device, message),
+            PulsarError::Stream(e) => write!(f, "cannot open audio stream: {}", e),
+            PulsarError::Sink(e) => write!(f, "cannot start audio playback: {}", e),
+            PulsarError::AudioThread(message) => write!(f, "audio thread: {}", message),
+            PulsarError::Terminal(e) => write!(f, "terminal error: {}", e),
+            PulsarError::Input(message) => write!(f, "input backend: {}", message),
+            PulsarError::Midi(message) => write!(f, "MIDI input: {}", message),
+            PulsarError::KeyMap { path, issues } => {
+                write!(f, "invalid key map {}", path)?;
+                for issue in issues {
+                    write!(f, "\n  {}", issue)?;
+                }
+                Ok(())
+            }
+            PulsarError::Io { path, source } => write!(f, "cannot read {}: {}", path, source),
+            PulsarError::Events { path, source } => write!(f, "{}: {}", path, source),
+            PulsarError::MidiFile { path, source } => write!(f, "{}: {}", path, source),
+            PulsarError::Wav { path, source } => write!(f, "cannot write {}: {}", path, source),
+        }
+    }
+}
+
+impl std::error::Error for PulsarError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PulsarError::Stream(e) => Some(e),
+            PulsarError::Sink(e) => Some(e),
+            PulsarError::Terminal(e) => Some(e),
+            PulsarError::Io { source, .. } => Some(source),
+            PulsarError::Events { source, .. } => Some(source),
+            PulsarError::MidiFile { source, .. } => Some(source),
+            PulsarError::Wav { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// crossterm reports every terminal failure as an io::Error
+impl From<io::Error> for PulsarError {
+    fn from(error: io::Error) -> Self {
+        PulsarError::Terminal(error)
+    }
+}
+
+impl From<rodio::StreamError> for PulsarError {
+    fn from(error: rodio::StreamError) -> Self {
+        PulsarError::Stream(error)
+    }
+}
+
+impl From<rodio::PlayError> for PulsarError {
+    fn from(error: rodio::PlayError) -> Self {
+        PulsarError::Sink(error)
+    }
+}
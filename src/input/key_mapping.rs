@@ -0,0 +1,341 @@
+use crate::synth::note::{Note, PitchClass};
+#[cfg(feature = "device_query")]
+use device_query::Keycode;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+// Physical keyboard layouts. Key positions are named by the character a US
+// QWERTY keyboard prints on them, which is also how device_query reports keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layout {
+    Qwerty,
+    Azerty,
+    Qwertz,
+    Dvorak,
+}
+
+// (US QWERTY position, character on the same key) for every key that differs
+const AZERTY: &[(char, char)] = &[
+    ('q', 'a'), ('w', 'z'), ('a', 'q'), ('z', 'w'), ('m', ','), (';', 'm'),
+    (',', ';'), ('.', ':'), ('/', '!'), ('1', '&'), ('2', 'é'), ('3', '"'),
+    ('4', '\''), ('5', '('), ('6', '-'), ('7', 'è'), ('8', '_'), ('9', 'ç'), ('0', 'à'),
+];
+const QWERTZ: &[(char, char)] = &[('y', 'z'), ('z', 'y')];
+const DVORAK: &[(char, char)] = &[
+    ('q', '\''), ('w', ','), ('e', '.'), ('r', 'p'), ('t', 'y'), ('y', 'f'), ('u', 'g'),
+    ('i', 'c'), ('o', 'r'), ('p', 'l'), ('[', '/'), (']', '='), ('s', 'o'), ('d', 'e'),
+    ('f', 'u'), ('g', 'i'), ('h', 'd'), ('j', 'h'), ('k', 't'), ('l', 'n'), (';', 's'),
+    ('\'', '-'), ('z', ';'), ('x', 'q'), ('c', 'j'), ('v', 'k'), ('b', 'x'), ('n', 'b'),
+    (',', 'w'), ('.', 'v'), ('/', 'z'), ('-', '['), ('=', ']'),
+];
+
+// Two rows laid out like a piano: the bottom letter row plays C3-B3 with the
+// row above it as black keys, the top letter row plays C4-E5 with the digits above
+const PRESET: &[(char, Note, i32)] = &[
+    ('z', Note::C, 3), ('s', Note::CSharp, 3), ('x', Note::D, 3), ('d', Note::DSharp, 3),
+    ('c', Note::E, 3), ('v', Note::F, 3), ('g', Note::FSharp, 3), ('b', Note::G, 3),
+    ('h', Note::GSharp, 3), ('n', Note::A, 3), ('j', Note::ASharp, 3), ('m', Note::B, 3),
+    ('q', Note::C, 4), ('2', Note::CSharp, 4), ('w', Note::D, 4), ('3', Note::DSharp, 4),
+    ('e', Note::E, 4), ('r', Note::F, 4), ('5', Note::FSharp, 4), ('t', Note::G, 4),
+    ('6', Note::GSharp, 4), ('y', Note::A, 4), ('7', Note::ASharp, 4), ('u', Note::B, 4),
+    ('i', Note::C, 5), ('9', Note::CSharp, 5), ('o', Note::D, 5), ('0', Note::DSharp, 5),
+    ('p', Note::E, 5),
+];
+
+impl Layout {
+    pub const ALL: [Layout; 4] = [Layout::Qwerty, Layout::Azerty, Layout::Qwertz, Layout::Dvorak];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "qwerty" => Some(Layout::Qwerty),
+            "azerty" => Some(Layout::Azerty),
+            "qwertz" => Some(Layout::Qwertz),
+            "dvorak" => Some(Layout::Dvorak),
+            _ => None,
+        }
+    }
+
+    // The character this layout prints on the key at a US QWERTY position
+    pub fn char_at(self, position: char) -> char {
+        let table = match self {
+            Layout::Qwerty => return position,
+            Layout::Azerty => AZERTY,
+            Layout::Qwertz => QWERTZ,
+            Layout::Dvorak => DVORAK,
+        };
+        table.iter()
+            .find(|(from, _)| *from == position)
+            .map_or(position, |(_, to)| *to)
+    }
+}
+
+// Something wrong with a key map file. Conflicts and syntax errors stop the map
+// from loading; the rest are reported but harmless.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyMapIssue {
+    Syntax { line: usize, message: String },
+    Conflict { key: char, line: usize, first_line: usize },
+    Duplicate { key: char, line: usize, first_line: usize },
+    SharedNote { note: PitchClass, keys: Vec<char> },
+}
+
+impl KeyMapIssue {
+    pub fn is_error(&self) -> bool {
+        matches!(self, KeyMapIssue::Syntax { .. } | KeyMapIssue::Conflict { .. })
+    }
+}
+
+impl fmt::Display for KeyMapIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyMapIssue::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            KeyMapIssue::Conflict { key, line, first_line } => {
+                write!(f, "line {}: `{}` is already mapped to a different note on line {}", line, key, first_line)
+            }
+            KeyMapIssue::Duplicate { key, line, first_line } => {
+                write!(f, "line {}: `{}` repeats the mapping from line {}", line, key, first_line)
+            }
+            KeyMapIssue::SharedNote { note, keys } => {
+                let keys: Vec<String> = keys.iter().map(|key| format!("`{}`", key)).collect();
+                write!(f, "{:?}{} is played by several keys: {}", note.note, note.octave, keys.join(", "))
+            }
+        }
+    }
+}
+
+// Which note each key plays, keyed by the lowercase character printed on the key
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyMap {
+    layout: Layout,
+    keys: HashMap<char, PitchClass>,
+}
+
+impl KeyMap {
+    pub fn preset(layout: Layout) -> Self {
+        let keys = PRESET.iter()
+            .map(|(position, note, octave)| (layout.char_at(*position), PitchClass::new(*note, *octave)))
+            .collect();
+        KeyMap { layout, keys }
+    }
+
+    // Parses a key map file, one `key = note` per line with `#` comments:
+    //
+    //     layout = qwertz    # start from this layout's preset
+    //     z = C3
+    //     s = C#3
+    //     ü = none           # unmap a key
+    //     # = D#3            # the `#` key itself
+    //
+    // A `#` only starts a comment at the start of a line or after whitespace, so
+    // sharp note names survive.
+    // On success the map comes with any warnings; on failure every issue found is returned.
+    pub fn parse(text: &str, layout: Layout) -> Result<(KeyMap, Vec<KeyMapIssue>), Vec<KeyMapIssue>> {
+        let mut issues = Vec::new();
+        let mut layout = layout;
+        let mut assignments: Vec<(char, Option<PitchClass>, usize)> = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let mut syntax = |message: String| issues.push(KeyMapIssue::Syntax { line: line_number, message });
+
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                syntax("expected `key = note`".to_string());
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            if key.eq_ignore_ascii_case("layout") {
+                match Layout::from_name(value) {
+                    Some(named) => layout = named,
+                    None => syntax(format!("unknown layout `{}`", value)),
+                }
+                continue;
+            }
+
+            let mut chars = key.chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                syntax(format!("`{}` is not a single key", key));
+                continue;
+            };
+            let note = if value.eq_ignore_ascii_case("none") {
+                None
+            } else {
+                match PitchClass::from_name(value).filter(|note| note.to_midi().is_some()) {
+                    Some(note) => Some(note),
+                    None => {
+                        syntax(format!("invalid note `{}`", value));
+                        continue;
+                    }
+                }
+            };
+            let key = normalize(c);
+
+            if let Some((_, first_note, first_line)) = assignments.iter().find(|(other, ..)| *other == key) {
+                issues.push(if *first_note == note {
+                    KeyMapIssue::Duplicate { key, line: line_number, first_line: *first_line }
+                } else {
+                    KeyMapIssue::Conflict { key, line: line_number, first_line: *first_line }
+                });
+                continue;
+            }
+            assignments.push((key, note, line_number));
+        }
+
+        let mut map = KeyMap::preset(layout);
+        for (key, note, _) in assignments {
+            match note {
+                Some(note) => map.keys.insert(key, note),
+                None => map.keys.remove(&key),
+            };
+        }
+        issues.extend(map.validate());
+
+        if issues.iter().any(KeyMapIssue::is_error) {
+            Err(issues)
+        } else {
+            Ok((map, issues))
+        }
+    }
+
+    // Reports notes reachable from more than one key
+    pub fn validate(&self) -> Vec<KeyMapIssue> {
+        let mut by_note: HashMap<PitchClass, Vec<char>> = HashMap::new();
+        for (key, note) in &self.keys {
+            by_note.entry(*note).or_default().push(*key);
+        }
+
+        let mut issues: Vec<KeyMapIssue> = by_note.into_iter()
+            .filter(|(_, keys)| keys.len() > 1)
+            .map(|(note, mut keys)| {
+                keys.sort_unstable();
+                KeyMapIssue::SharedNote { note, keys }
+            })
+            .collect();
+        issues.sort_by_key(|issue| match issue {
+            KeyMapIssue::SharedNote { note, .. } => note.to_midi(),
+            _ => None,
+        });
+        issues
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn get_pitch_class(&self, key: char) -> Option<&PitchClass> {
+        self.keys.get(&normalize(key))
+    }
+
+    pub fn get_midi_note(&self, key: char) -> Option<u8> {
+        self.get_pitch_class(key).and_then(PitchClass::to_midi)
+    }
+
+    // For backends that report physical key positions rather than characters
+    pub fn get_midi_note_at(&self, position: char) -> Option<u8> {
+        self.get_midi_note(self.layout.char_at(position))
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    // `# = note` maps the `#` key rather than being a comment
+    let trimmed = line.trim_start();
+    let (offset, rest) = match trimmed.strip_prefix('#') {
+        Some(after) if after.trim_start().starts_with('=') => (line.len() - after.len(), after),
+        _ => (0, line),
+    };
+
+    let mut previous = None;
+    for (index, c) in rest.char_indices() {
+        if c == '#' && previous.is_none_or(char::is_whitespace) {
+            return &line[..offset + index];
+        }
+        previous = Some(c);
+    }
+    line
+}
+
+fn normalize(key: char) -> char {
+    key.to_lowercase().next().unwrap_or(key)
+}
+
+static ACTIVE: OnceLock<KeyMap> = OnceLock::new();
+
+// Sets the map used by `get_pitch_class` and `get_midi_note`. Only the first call
+// takes effect, so this belongs at startup before any input is read.
+pub fn install(map: KeyMap) -> bool {
+    ACTIVE.set(map).is_ok()
+}
+
+// The installed map, or the QWERTY preset if none was installed
+pub fn active() -> &'static KeyMap {
+    ACTIVE.get_or_init(|| KeyMap::preset(Layout::Qwerty))
+}
+
+pub fn get_pitch_class(key: char) -> Option<&'static PitchClass> {
+    active().get_pitch_class(key)
+}
+
+pub fn get_midi_note(key: char) -> Option<u8> {
+    active().get_midi_note(key)
+}
+
+// The US QWERTY position a device_query key code stands for, for the keys a map can use
+#[cfg(feature = "device_query")]
+pub fn keycode_char(key: &Keycode) -> Option<char> {
+    let c = match key {
+        Keycode::Key0 => '0',
+        Keycode::Key1 => '1',
+        Keycode::Key2 => '2',
+        Keycode::Key3 => '3',
+        Keycode::Key4 => '4',
+        Keycode::Key5 => '5',
+        Keycode::Key6 => '6',
+        Keycode::Key7 => '7',
+        Keycode::Key8 => '8',
+        Keycode::Key9 => '9',
+        Keycode::A => 'a',
+        Keycode::B => 'b',
+        Keycode::C => 'c',
+        Keycode::D => 'd',
+        Keycode::E => 'e',
+        Keycode::F => 'f',
+        Keycode::G => 'g',
+        Keycode::H => 'h',
+        Keycode::I => 'i',
+        Keycode::J => 'j',
+        Keycode::K => 'k',
+        Keycode::L => 'l',
+        Keycode::M => 'm',
+        Keycode::N => 'n',
+        Keycode::O => 'o',
+        Keycode::P => 'p',
+        Keycode::Q => 'q',
+        Keycode::R => 'r',
+        Keycode::S => 's',
+        Keycode::T => 't',
+        Keycode::U => 'u',
+        Keycode::V => 'v',
+        Keycode::W => 'w',
+        Keycode::X => 'x',
+        Keycode::Y => 'y',
+        Keycode::Z => 'z',
+        Keycode::Grave => '`',
+        Keycode::Minus => '-',
+        Keycode::Equal => '=',
+        Keycode::LeftBracket => '[',
+        Keycode::RightBracket => ']',
+        Keycode::BackSlash => '\\',
+        Keycode::Semicolon => ';',
+        Keycode::Apostrophe => '\'',
+        Keycode::Comma => ',',
+        Keycode::Dot => '.',
+        Keycode::Slash => '/',
+        _ => return None,
+    };
+    Some(c)
+}
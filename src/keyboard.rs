@@ -0,0 +1,297 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use device_query::{DeviceQuery, DeviceState, Keycode};
+use pulsar::input::key_mapping::{self, get_midi_note, keycode_char};
+
+// Without release events a note is released once its key stops auto-repeating.
+// This has to outlast the typical delay before auto-repeat starts.
+const RELEASE_TIMEOUT: Duration = Duration::from_millis(600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    // Terminal events if the terminal reports key releases, else device_query if available
+    Auto,
+    Terminal,
+    DeviceQuery,
+}
+
+impl InputKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(InputKind::Auto),
+            "terminal" => Some(InputKind::Terminal),
+            "device_query" | "device-query" => Some(InputKind::DeviceQuery),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    NoteOn(u8),
+    NoteOff(u8),
+    ToggleWaveform,
+    ToggleQuality,
+}
+
+// Offset applied to every mapped key: Left/Right shift by octaves, Down/Up by semitones
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Transpose {
+    pub octaves:    i32,
+    pub semitones:  i32,
+}
+
+impl Transpose {
+    const MAX_OCTAVES: i32 = 4;
+    const MAX_SEMITONES: i32 = 11;
+
+    // None if the shifted note falls outside the MIDI range
+    pub fn apply(&self, note: u8) -> Option<u8> {
+        let shifted = note as i32 + self.octaves * 12 + self.semitones;
+        u8::try_from(shifted).ok().filter(|note| *note < 128)
+    }
+
+    fn shift_octaves(&mut self, delta: i32) {
+        self.octaves = (self.octaves + delta).clamp(-Self::MAX_OCTAVES, Self::MAX_OCTAVES);
+    }
+
+    fn shift_semitones(&mut self, delta: i32) {
+        self.semitones = (self.semitones + delta).clamp(-Self::MAX_SEMITONES, Self::MAX_SEMITONES);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shift {
+    OctaveDown,
+    OctaveUp,
+    SemitoneDown,
+    SemitoneUp,
+}
+
+// Keys currently playing, each with the note it started. Releases send that note
+// rather than looking the key up again, so shifting while keys are held never
+// leaves a note hanging.
+struct HeldNotes<K> {
+    notes:      HashMap<K, u8>,
+    transpose:  Transpose,
+}
+
+impl<K: Copy + Eq + Hash> HeldNotes<K> {
+    fn new() -> Self {
+        HeldNotes {
+            notes: HashMap::new(),
+            transpose: Transpose::default(),
+        }
+    }
+
+    fn is_held(&self, key: K) -> bool {
+        self.notes.contains_key(&key)
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &K> {
+        self.notes.keys()
+    }
+
+    fn note_held(&self, note: u8) -> bool {
+        self.notes.values().any(|held| *held == note)
+    }
+
+    fn press(&mut self, key: K, base_note: u8, actions: &mut Vec<KeyAction>) {
+        let Some(note) = self.transpose.apply(base_note) else { return };
+        // Several keys can map to the same note; only the first one starts it
+        if !self.note_held(note) {
+            actions.push(KeyAction::NoteOn(note));
+        }
+        self.notes.insert(key, note);
+    }
+
+    fn release(&mut self, key: K, actions: &mut Vec<KeyAction>) {
+        // ...and only the last one releases it
+        if let Some(note) = self.notes.remove(&key) {
+            if !self.note_held(note) {
+                actions.push(KeyAction::NoteOff(note));
+            }
+        }
+    }
+
+    fn shift(&mut self, shift: Shift) {
+        match shift {
+            Shift::OctaveDown => self.transpose.shift_octaves(-1),
+            Shift::OctaveUp => self.transpose.shift_octaves(1),
+            Shift::SemitoneDown => self.transpose.shift_semitones(-1),
+            Shift::SemitoneUp => self.transpose.shift_semitones(1),
+        }
+    }
+}
+
+// A source of keyboard input for playing notes
+pub trait Keyboard {
+    fn name(&self) -> &'static str;
+
+    // Every key event read from the terminal passes through here, whichever backend is active
+    fn handle_event(&mut self, event: &KeyEvent, actions: &mut Vec<KeyAction>);
+
+    // Called on every pass of the input loop
+    fn poll(&mut self, actions: &mut Vec<KeyAction>);
+
+    fn transpose(&self) -> Transpose;
+}
+
+// Reads key press, repeat and release events from the terminal, so it works over
+// SSH and on Wayland and only sees keys typed into Pulsar's own window
+pub struct TerminalKeyboard {
+    held:               HeldNotes<char>,
+    last_seen:          HashMap<char, Instant>,
+    reports_release:    bool,
+}
+
+impl TerminalKeyboard {
+    // `reports_release` is whether keyboard enhancement is active, i.e. whether
+    // the terminal sends release events at all
+    pub fn new(reports_release: bool) -> Self {
+        TerminalKeyboard {
+            held: HeldNotes::new(),
+            last_seen: HashMap::new(),
+            reports_release,
+        }
+    }
+
+    fn release(&mut self, key: char, actions: &mut Vec<KeyAction>) {
+        self.last_seen.remove(&key);
+        self.held.release(key, actions);
+    }
+}
+
+impl Keyboard for TerminalKeyboard {
+    fn name(&self) -> &'static str {
+        if self.reports_release { "terminal" } else { "terminal (no key release events)" }
+    }
+
+    fn handle_event(&mut self, event: &KeyEvent, actions: &mut Vec<KeyAction>) {
+        let shift = match event.code {
+            KeyCode::Left => Some(Shift::OctaveDown),
+            KeyCode::Right => Some(Shift::OctaveUp),
+            KeyCode::Down => Some(Shift::SemitoneDown),
+            KeyCode::Up => Some(Shift::SemitoneUp),
+            _ => None,
+        };
+        if let Some(shift) = shift {
+            if event.kind == KeyEventKind::Press {
+                self.held.shift(shift);
+            }
+            return;
+        }
+
+        match (event.code, event.kind) {
+            (KeyCode::Char(' '), KeyEventKind::Press) => actions.push(KeyAction::ToggleWaveform),
+            (KeyCode::Tab, KeyEventKind::Press) => actions.push(KeyAction::ToggleQuality),
+            (KeyCode::Char(c), KeyEventKind::Release) => self.release(c.to_ascii_lowercase(), actions),
+            (KeyCode::Char(c), _) => {
+                let key = c.to_ascii_lowercase();
+                // Legacy terminals report auto-repeat as further presses
+                if self.held.is_held(key) {
+                    self.last_seen.insert(key, Instant::now());
+                    return;
+                }
+                if let Some(note) = get_midi_note(key) {
+                    self.held.press(key, note, actions);
+                    self.last_seen.insert(key, Instant::now());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn poll(&mut self, actions: &mut Vec<KeyAction>) {
+        if self.reports_release {
+            return;
+        }
+
+        let now = Instant::now();
+        let expired: Vec<char> = self.held.keys()
+            .filter(|key| self.last_seen.get(key).is_none_or(|seen| now.duration_since(*seen) > RELEASE_TIMEOUT))
+            .copied()
+            .collect();
+        for key in expired {
+            self.release(key, actions);
+        }
+    }
+
+    fn transpose(&self) -> Transpose {
+        self.held.transpose
+    }
+}
+
+// Polls the global keyboard state. Needs an X11 display and also sees keys
+// pressed while another window has focus. Keys are reported by position, so
+// the key map's layout decides which character each one stands for.
+pub struct DeviceQueryKeyboard {
+    device_state:   DeviceState,
+    last_keys:      HashSet<Keycode>,
+    held:           HeldNotes<Keycode>,
+}
+
+impl DeviceQueryKeyboard {
+    pub fn new() -> Option<Self> {
+        Some(DeviceQueryKeyboard {
+            device_state: DeviceState::checked_new()?,
+            last_keys: HashSet::new(),
+            held: HeldNotes::new(),
+        })
+    }
+
+    fn pressed(&self, keys: &HashSet<Keycode>, key: Keycode) -> bool {
+        keys.contains(&key) && !self.last_keys.contains(&key)
+    }
+}
+
+impl Keyboard for DeviceQueryKeyboard {
+    fn name(&self) -> &'static str {
+        "device_query"
+    }
+
+    fn handle_event(&mut self, _event: &KeyEvent, _actions: &mut Vec<KeyAction>) {}
+
+    fn poll(&mut self, actions: &mut Vec<KeyAction>) {
+        let keys: HashSet<Keycode> = self.device_state.get_keys().into_iter().collect();
+
+        for key in self.last_keys.difference(&keys) {
+            self.held.release(*key, actions);
+        }
+
+        let shifts = [
+            (Keycode::Left, Shift::OctaveDown),
+            (Keycode::Right, Shift::OctaveUp),
+            (Keycode::Down, Shift::SemitoneDown),
+            (Keycode::Up, Shift::SemitoneUp),
+        ];
+        for (key, shift) in shifts {
+            if self.pressed(&keys, key) {
+                self.held.shift(shift);
+            }
+        }
+
+        let key_map = key_mapping::active();
+        for key in keys.difference(&self.last_keys) {
+            if let Some(note) = keycode_char(key).and_then(|position| key_map.get_midi_note_at(position)) {
+                self.held.press(*key, note, actions);
+            }
+        }
+
+        if self.pressed(&keys, Keycode::Space) {
+            actions.push(KeyAction::ToggleWaveform);
+        }
+
+        if self.pressed(&keys, Keycode::Tab) {
+            actions.push(KeyAction::ToggleQuality);
+        }
+
+        self.last_keys = keys;
+    }
+
+    fn transpose(&self) -> Transpose {
+        self.held.transpose
+    }
+}
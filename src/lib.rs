@@ -0,0 +1,10 @@
+#[allow(clippy::module_inception)]
+pub mod synth;
+pub mod input;
+pub mod alloc_guard;
+pub mod render;
+pub mod midi;
+
+pub use synth::{
+    adsr::ADSR, waveform::{Quality, WaveForm}, Command, StealPolicy, StopSignal, Synth, SynthController, SynthSource,
+};
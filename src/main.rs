@@ -1,40 +1,48 @@
-mod synth;
+mod audio_device;
+mod capture;
+mod config;
+mod detune_slider;
+mod error;
+mod keyboard;
+mod midi_input;
+mod terminal;
 
 use std::{
-    collections::HashSet,
-    io::stdout,
-    sync::Arc,
-    time::Duration,
+    env,
+    fs,
+    io::{stdout, Write},
+    path::PathBuf,
+    process::ExitCode,
+    time::{Duration, Instant},
     thread,
 };
-use parking_lot::Mutex;
-use device_query::{DeviceQuery, DeviceState, Keycode};
-use rodio::{Sink, OutputStream};
-use synth::{adsr::ADSR, Slider, Synth, SynthSource};
+use rodio::Sink;
+use pulsar::input::key_mapping::{self, KeyMap};
+use pulsar::midi::MidiFile;
+use pulsar::render::{self, SampleFormat, TimedEvent};
+use pulsar::synth::{adsr::ADSR, audio_tap, Command, Synth, SynthController, SynthSource, CHANNELS};
+use audio_device::AudioOutput;
+use capture::{MidiCapture, WavCapture};
+use config::{Config, DEFAULT_SAMPLE_RATE, USAGE};
+use detune_slider::Slider;
+use error::PulsarError;
+use keyboard::{DeviceQueryKeyboard, InputKind, KeyAction, Keyboard, TerminalKeyboard};
+use midi_input::MidiInput;
+use terminal::TerminalGuard;
 use crossterm::{
-    cursor::{Hide, MoveTo, Show},
-    event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event,
-        MouseButton, MouseEvent, MouseEventKind, KeyCode
-    },
+    cursor::MoveTo,
+    event::{self, Event, KeyCode, KeyEventKind, MouseButton, MouseEvent, MouseEventKind},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType}
+    terminal::{Clear, ClearType}
 };
 
-fn main() {
-    let slider_width = 100;
-    let mut detune_slider = Slider::new(0.0, 1.0, slider_width);
-    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-    let sink = Sink::try_new(&stream_handle).unwrap();
-
-    enable_raw_mode().unwrap();
-    execute!(stdout(), EnableMouseCapture, Hide).unwrap();
+// Upper bound on how long quitting waits for the audio to fade out
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(500);
 
-    // Clear the terminal screen at the start
-    execute!(stdout(), Clear(ClearType::All)).unwrap();
+// How long `play` lets notes ring on after the last event
+const PLAY_TAIL_TIMEOUT: Duration = Duration::from_secs(10);
 
-    let sample_rate = 44100;
-    
+fn build_synth(sample_rate: u32) -> Synth {
     // Set ADSR with duration values; ensure `ADSR` struct handles `Duration` correctly if needed
     let adsr = ADSR::new(
         100,      // Attack (ms)
@@ -43,39 +51,290 @@ fn main() {
         350,      // Release (ms)
     );
     
-    let synth = Arc::new(Mutex::new(Synth::new(
+    let mut synth = Synth::new(
         sample_rate as f32,
         adsr,
-    )));
-    
-    // Audio thread to handle SynthSource with Rodio Sink
-    let audio_synth = Arc::clone(&synth);
+    );
+    synth.set_master_volume(0.8);
+    synth
+}
+
+// Standard MIDI Files are recognised by extension; anything else is the plain-text event format
+fn load_events(path: &str) -> Result<Vec<TimedEvent>, PulsarError> {
+    let extension = path.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+    if matches!(extension.as_deref(), Some("mid" | "midi" | "smf")) {
+        let file = MidiFile::load(path)
+            .map_err(|source| PulsarError::MidiFile { path: path.to_string(), source })?;
+        return Ok(file.timed_events());
+    }
+
+    let text = fs::read_to_string(path)
+        .map_err(|source| PulsarError::Io { path: path.to_string(), source })?;
+    render::parse_events(&text)
+        .map_err(|source| PulsarError::Events { path: path.to_string(), source })
+}
+
+// pulsar render <events.txt|song.mid> <out.wav> [--format 16|24|32] [--sample-rate HZ]
+fn render_command(args: &[String]) -> Result<(), PulsarError> {
+    let usage = || PulsarError::Usage(USAGE.to_string());
+    let mut paths = Vec::new();
+    let mut format = SampleFormat::Int16;
+    let mut sample_rate = DEFAULT_SAMPLE_RATE;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--format" {
+            let value = args.next().ok_or_else(usage)?;
+            format = SampleFormat::parse(value)
+                .ok_or_else(|| PulsarError::Usage(format!("unknown format `{}`", value)))?;
+        } else if arg == "--sample-rate" {
+            let value = args.next().ok_or_else(usage)?;
+            sample_rate = value.parse().ok()
+                .filter(|rate| *rate > 0)
+                .ok_or_else(|| PulsarError::Usage(format!("invalid sample rate `{}`", value)))?;
+        } else {
+            paths.push(arg);
+        }
+    }
+    let [events_path, output_path] = paths[..] else {
+        return Err(usage());
+    };
+
+    let events = load_events(events_path)?;
+
+    let mut synth = build_synth(sample_rate);
+    let samples = render::render(&mut synth, &events);
+    render::write_wav(output_path, &samples, CHANNELS as u16, sample_rate, format)
+        .map_err(|source| PulsarError::Wav { path: output_path.clone(), source })?;
+
+    let seconds = (samples.len() / CHANNELS) as f32 / sample_rate as f32;
+    println!("Rendered {:.2}s to {}", seconds, output_path);
+    Ok(())
+}
+
+// pulsar play <song.mid|events.txt> [audio options]
+// Plays the events through the output device in real time, then waits for the release tails
+fn play_command(args: &[String]) -> Result<(), PulsarError> {
+    let Some((path, options)) = args.split_first() else {
+        return Err(PulsarError::Usage(USAGE.to_string()));
+    };
+    let config = Config::load(options)?;
+    let events = load_events(path)?;
+
+    let AudioOutput { stream: _stream, handle, sample_rate, .. } = audio_device::open(&config)?;
+    let sink = Sink::try_new(&handle)?;
+    let (source, mut controller) = SynthSource::with_block_size(build_synth(sample_rate), sample_rate, config.render_block);
+    sink.append(source);
+
+    let duration = events.last().map_or(0.0, |event| event.time);
+    println!("Playing {} ({:.1}s)", path, duration);
+
+    // A full queue means the device has stopped pulling audio; events are dropped
+    // rather than waited on so playback can't hang
+    let start = Instant::now();
+    let mut dropped = 0;
+    for event in &events {
+        let due = start + Duration::from_secs_f64(event.time);
+        thread::sleep(due.saturating_duration_since(Instant::now()));
+        if !controller.send(event.command) {
+            dropped += 1;
+        }
+    }
+    if dropped > 0 {
+        eprintln!("pulsar: dropped {} of {} events because the audio device fell behind", dropped, events.len());
+    }
+
+    // Give the last commands a block or two to land before watching the voice count
+    thread::sleep(Duration::from_millis(50));
+    let tail_deadline = Instant::now() + PLAY_TAIL_TIMEOUT;
+    while controller.snapshot().active_voices > 0 && Instant::now() < tail_deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    controller.stop();
+    let stop_deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    while !sink.empty() && Instant::now() < stop_deadline {
+        thread::sleep(Duration::from_millis(1));
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("render") => render_command(&args[1..]),
+        Some("play") => play_command(&args[1..]),
+        _ => run(&args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("pulsar: {}", error);
+            ExitCode::from(error.exit_code())
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), PulsarError> {
+    let config = Config::load(args)?;
+    if config.list_devices {
+        return audio_device::print_devices();
+    }
+    key_mapping::install(load_key_map(&config)?);
+    let midi = config.midi.as_deref().map(midi_input::open).transpose()?;
+
+    // The stream must stay alive for as long as anything is playing
+    let AudioOutput { stream: _stream, handle, device_name, sample_rate } = audio_device::open(&config)?;
+    let sink = Sink::try_new(&handle)?;
+
+    // Render at whatever rate the device actually runs at, so nothing is resampled or detuned
+    let synth = build_synth(sample_rate);
+
+    // The audio thread owns the synth; the input loop only talks to it through the controller
+    let (mut source, mut controller) = SynthSource::with_block_size(synth, sample_rate, config.render_block);
+
+    // Recording taps the output after clipping, so the file holds exactly what was heard.
+    // The tap buffers a second of audio in case the writer thread is held up.
+    let (tap, tap_reader) = audio_tap(sample_rate as usize * CHANNELS);
+    source.set_tap(tap);
+    let mut wav_capture = WavCapture::new(tap_reader, sample_rate);
+
+    // Audio thread to handle SynthSource with Rodio Sink. It exits once the source
+    // has faded out after a stop, or after SHUTDOWN_TIMEOUT if the device stalls.
+    let stop_signal = controller.stop_signal();
     let audio_thread = thread::Builder::new()
         .name("audio_processing".to_string())
         .spawn(move || {
-            let source = SynthSource::new(audio_synth, sample_rate);
             sink.set_volume(1.0);
             sink.append(source);
             sink.play();
-            
+
+            let mut deadline = None;
             while !sink.empty() {
+                if stop_signal.is_requested() {
+                    let deadline = *deadline.get_or_insert_with(|| Instant::now() + SHUTDOWN_TIMEOUT);
+                    if Instant::now() >= deadline {
+                        break;
+                    }
+                }
                 thread::sleep(Duration::from_micros(500));
             }
+            sink.stop();
         })
-        .unwrap();
+        .map_err(|e| PulsarError::AudioThread(e.to_string()))?;
+
+    let mut capture = None;
+    let result = TerminalGuard::enter()
+        .map_err(PulsarError::from)
+        .and_then(|terminal| {
+            let mut keyboard = open_keyboard(config.input, &terminal)?;
+            let mut status = format!(
+                " {} @ {} Hz, rendering {} frame blocks, input: {}",
+                device_name, sample_rate, config.render_block, keyboard.name()
+            );
+            if let Some(midi) = &midi {
+                status.push_str(&format!(", MIDI: {}", midi.name));
+            }
+            let mut captures = Captures { midi: &mut capture, wav: &mut wav_capture, dir: &config.record_dir };
+            input_loop(&mut controller, keyboard.as_mut(), midi.as_ref(), &mut captures, &status)
+        });
 
-    let device_state = DeviceState::new();
-    let mut last_keys: HashSet<Keycode> = HashSet::new();
+    // Recordings still running at quit are saved rather than lost. A failed save is
+    // reported here rather than returned, so the shutdown below always runs.
+    if let Some(capture) = capture {
+        match capture.finish() {
+            Ok(path) => println!("Saved recording to {}", path.display()),
+            Err(error) => eprintln!("pulsar: recording failed: {}", error),
+        }
+    }
+    if let Some(saved) = wav_capture.stop() {
+        println!("{}", wav_notice(saved).trim_start());
+    }
+
+    // Fade out and let the source end, which empties the sink and ends the audio thread.
+    // This runs even when the input loop failed so the audio never outlives the UI.
+    controller.stop();
+    audio_thread.join().map_err(|_| PulsarError::AudioThread("panicked".to_string()))?;
+    result
+}
+
+// Starts from the configured layout's preset, with the key map file (if any) on top
+fn load_key_map(config: &Config) -> Result<KeyMap, PulsarError> {
+    let Some(path) = &config.keymap else {
+        return Ok(KeyMap::preset(config.layout));
+    };
+
+    let text = fs::read_to_string(path)
+        .map_err(|source| PulsarError::Io { path: path.clone(), source })?;
+    let (map, warnings) = KeyMap::parse(&text, config.layout)
+        .map_err(|issues| PulsarError::KeyMap { path: path.clone(), issues })?;
+    for warning in warnings {
+        eprintln!("pulsar: {}: {}", path, warning);
+    }
+    Ok(map)
+}
+
+// Terminal input needs keyboard enhancement to see key releases; without it, auto
+// prefers device_query and only falls back to release timeouts if that is unavailable
+fn open_keyboard(kind: InputKind, terminal: &TerminalGuard) -> Result<Box<dyn Keyboard>, PulsarError> {
+    let device_query = || {
+        DeviceQueryKeyboard::new()
+            .ok_or_else(|| PulsarError::Input("device_query needs an X11 display; try --input terminal".to_string()))
+    };
+
+    let keyboard: Box<dyn Keyboard> = match kind {
+        InputKind::Terminal => Box::new(TerminalKeyboard::new(terminal.enable_keyboard_enhancement()?)),
+        InputKind::DeviceQuery => Box::new(device_query()?),
+        InputKind::Auto => {
+            if terminal.enable_keyboard_enhancement()? {
+                Box::new(TerminalKeyboard::new(true))
+            } else if let Ok(keyboard) = device_query() {
+                Box::new(keyboard)
+            } else {
+                Box::new(TerminalKeyboard::new(false))
+            }
+        }
+    };
+    Ok(keyboard)
+}
+
+// Recordings the input loop can start and stop, and where new ones are saved
+struct Captures<'a> {
+    midi:   &'a mut Option<MidiCapture>,
+    wav:    &'a mut WavCapture,
+    dir:    &'a str,
+}
+
+// Input loop handling keys, mouse, and envelope updates; returns when Esc is pressed.
+// While a MIDI capture is running, everything sent to the synth is recorded into it.
+fn input_loop(
+    controller: &mut SynthController,
+    keyboard: &mut dyn Keyboard,
+    midi: Option<&MidiInput>,
+    captures: &mut Captures,
+    status: &str,
+) -> Result<(), PulsarError> {
+    let slider_width = 100;
+    let mut detune_slider = Slider::new(0.0, 1.0, slider_width);
+    let mut actions = Vec::new();
     let mut is_dragging = false;
-    
-    // Input loop handling keys, mouse, and envelope updates
-    'main: loop {
-        if event::poll(Duration::from_micros(100)).unwrap() {
-            execute!(stdout(), MoveTo(0, 0), Clear(ClearType::CurrentLine)).unwrap();
+    let mut shown_seconds = 0;
+    // Kept here rather than read back from a snapshot, which lags the commands still
+    // queued and would let two quick toggles land on the same waveform
+    let mut waveform = controller.snapshot().waveform;
+
+    execute!(stdout(), MoveTo(0, 2))?;
+    print!("{}", status);
+    stdout().flush()?;
 
-            detune_slider.draw("Detune");
+    loop {
+        let mut redraw = false;
 
-            match event::read().unwrap() {
+        if event::poll(Duration::from_micros(100))? {
+            redraw = true;
+
+            match event::read()? {
                 Event::Mouse(MouseEvent { kind, column, row, .. }) => {
                     match kind {
                         MouseEventKind::Down(MouseButton::Left) => {
@@ -94,57 +353,135 @@ fn main() {
                         if column >= min_column && column <= max_column && row == 0 {
                             let pos = (column - min_column) as usize;
                             detune_slider.update_value(pos);
-                            let detune_value = detune_slider.get_value();
-                            let mut synth = synth.lock();
-                            synth.set_detune(detune_value);
+                            let command = Command::SetDetune(detune_slider.get_value());
+                            send(controller, captures.midi, command);
                         }
                     }
                 }
                 Event::Key(key_event) => {
-                    if let KeyCode::Esc = key_event.code {
-                        break 'main;
+                    match key_event.code {
+                        KeyCode::Esc => return Ok(()),
+                        KeyCode::F(key @ (5 | 6)) => {
+                            if key_event.kind == KeyEventKind::Press {
+                                let notice = if key == 5 {
+                                    toggle_capture(captures.midi, captures.dir)
+                                } else {
+                                    toggle_wav_capture(captures.wav, captures.dir)
+                                };
+                                execute!(stdout(), MoveTo(0, 3), Clear(ClearType::CurrentLine))?;
+                                print!("{}", notice);
+                            }
+                        }
+                        _ => keyboard.handle_event(&key_event, &mut actions),
                     }
                 }
                 _ => {}
             }
         }
 
-        // Handle key inputs
-        let keys: HashSet<Keycode> = device_state.get_keys().into_iter().collect();
-        let mut synth = synth.lock();
-        
-        // Direct envelope updates
-        synth.update_envelope();
-        synth.set_master_volume(0.8);
-        
-        // Add or remove notes based on key differences
-        for key in keys.difference(&last_keys) {
-            synth.add_note(*key);
+        keyboard.poll(&mut actions);
+        redraw |= !actions.is_empty();
+        for action in actions.drain(..) {
+            let command = match action {
+                KeyAction::NoteOn(note) => Command::NoteOn { note, velocity: 127 },
+                KeyAction::NoteOff(note) => Command::NoteOff { note },
+                // Sent as the waveform it lands on, so a recording can play it back
+                KeyAction::ToggleWaveform => {
+                    waveform.toggle();
+                    Command::SetWaveform(waveform)
+                }
+                KeyAction::ToggleQuality => Command::ToggleQuality,
+            };
+            send(controller, captures.midi, command);
         }
-        
-        for key in last_keys.difference(&keys) {
-            synth.remove_note(*key);
+
+        if let Some(midi) = midi {
+            for message in midi.messages.try_iter() {
+                if let Some(command) = message.to_command() {
+                    if let Command::SetWaveform(program) = command {
+                        waveform = program;
+                    }
+                    controller.send(command);
+                    redraw = true;
+                }
+                if let Some(capture) = captures.midi.as_mut() {
+                    capture.record_message(message);
+                }
+            }
         }
-        
-        if keys.contains(&Keycode::Space) && !last_keys.contains(&Keycode::Space) {
-            synth.toggle_waveform();
+
+        // The recording length ticks over once a second
+        let seconds = captures.wav.seconds() as u64;
+        redraw |= seconds != shown_seconds;
+        shown_seconds = seconds;
+
+        // Drawn after the input is handled so a shift shows up straight away
+        if redraw {
+            execute!(stdout(), MoveTo(0, 0), Clear(ClearType::CurrentLine))?;
+
+            detune_slider.draw("Detune")?;
+
+            let snapshot = controller.snapshot();
+            let transpose = keyboard.transpose();
+            execute!(stdout(), MoveTo(0, 1), Clear(ClearType::CurrentLine))?;
+            print!(
+                " Waveform: {:?} ({:?})  Voices: {}/{}  Octave: {:+}  Transpose: {:+}",
+                snapshot.waveform, snapshot.quality, snapshot.active_voices, snapshot.max_polyphony,
+                transpose.octaves, transpose.semitones
+            );
+            if captures.midi.is_some() {
+                print!("  REC MIDI");
+            }
+            if captures.wav.is_recording() {
+                print!("  REC WAV {}:{:02}", seconds / 60, seconds % 60);
+            }
+            stdout().flush()?;
         }
 
-        last_keys = keys;
-        drop(synth);
-        
         thread::sleep(Duration::from_micros(100));
     }
+}
 
-    // Cleanup
-    execute!(stdout(), DisableMouseCapture, Show).unwrap();
-    disable_raw_mode().unwrap();
-    
-    // Clear active keys and envelopes before exiting
-    let mut synth = synth.lock();
-    synth.active_keys.clear();
-    synth.key_envelopes.clear();
-    drop(synth);
-    
-    audio_thread.join().unwrap();
-}
\ No newline at end of file
+fn send(controller: &mut SynthController, capture: &mut Option<MidiCapture>, command: Command) {
+    if controller.send(command) {
+        if let Some(capture) = capture.as_mut() {
+            capture.record_command(&command);
+        }
+    }
+}
+
+// Starts a recording, or stops the current one and saves it; returns a line for the status area
+fn toggle_capture(capture: &mut Option<MidiCapture>, record_dir: &str) -> String {
+    match capture.take() {
+        Some(finished) => match finished.finish() {
+            Ok(path) => format!(" Saved recording to {}", path.display()),
+            Err(error) => format!(" Recording failed: {}", error),
+        },
+        None => match MidiCapture::start(record_dir) {
+            Ok(started) => {
+                let notice = format!(" Recording to {}", started.path().display());
+                *capture = Some(started);
+                notice
+            }
+            Err(error) => format!(" Recording failed: {}", error),
+        },
+    }
+}
+
+fn toggle_wav_capture(capture: &mut WavCapture, record_dir: &str) -> String {
+    if let Some(saved) = capture.stop() {
+        return wav_notice(saved);
+    }
+    match capture.start(record_dir) {
+        Ok(path) => format!(" Recording audio to {}", path.display()),
+        Err(error) => format!(" Recording failed: {}", error),
+    }
+}
+
+fn wav_notice(saved: Result<(PathBuf, u64), PulsarError>) -> String {
+    match saved {
+        Ok((path, 0)) => format!(" Saved audio to {}", path.display()),
+        Ok((path, dropped)) => format!(" Saved audio to {} ({} samples lost while the disk was busy)", path.display(), dropped),
+        Err(error) => format!(" Recording failed: {}", error),
+    }
+}
@@ -0,0 +1,96 @@
+use crate::synth::synth::{
+    control_from_cutoff, CC_ALL_NOTES_OFF, CC_ALL_SOUND_OFF, CC_CUTOFF, CC_DETUNE, CC_MOD_WHEEL, CC_PAN, CC_RESONANCE, CC_SUSTAIN, CC_VOLUME,
+};
+use crate::synth::waveform::WaveForm;
+use crate::synth::Command;
+
+// Channel voice messages from a MIDI 1.0 stream. Channels are 0-15; a note on
+// with velocity 0 is delivered as a note off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOff { channel: u8, note: u8, velocity: u8 },
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    PolyPressure { channel: u8, note: u8, pressure: u8 },
+    ControlChange { channel: u8, controller: u8, value: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    ChannelPressure { channel: u8, pressure: u8 },
+    // -8192 to 8191, 0 is centred
+    PitchBend { channel: u8, value: i16 },
+}
+
+impl MidiMessage {
+    pub fn channel(&self) -> u8 {
+        match *self {
+            MidiMessage::NoteOff { channel, .. }
+            | MidiMessage::NoteOn { channel, .. }
+            | MidiMessage::PolyPressure { channel, .. }
+            | MidiMessage::ControlChange { channel, .. }
+            | MidiMessage::ProgramChange { channel, .. }
+            | MidiMessage::ChannelPressure { channel, .. }
+            | MidiMessage::PitchBend { channel, .. } => channel,
+        }
+    }
+
+    // The synth command this message drives, if the synth responds to it
+    pub fn to_command(&self) -> Option<Command> {
+        match *self {
+            MidiMessage::NoteOn { note, velocity, .. } => Some(Command::NoteOn { note, velocity }),
+            MidiMessage::NoteOff { note, .. } => Some(Command::NoteOff { note }),
+            MidiMessage::ControlChange { controller, value, .. } => Some(Command::ControlChange { controller, value }),
+            MidiMessage::PitchBend { value, .. } => Some(Command::PitchBend(value as f32 / 8192.0)),
+            MidiMessage::ProgramChange { program, .. } => {
+                WaveForm::ALL.get(program as usize).map(|waveform| Command::SetWaveform(*waveform))
+            }
+            _ => None,
+        }
+    }
+
+    // The inverse of `to_command`, on channel 0, for recording what was played.
+    // Parameters without a MIDI equivalent give None.
+    pub fn from_command(command: &Command) -> Option<MidiMessage> {
+        let channel = 0;
+        let control = |controller: u8, amount: f32| MidiMessage::ControlChange {
+            channel,
+            controller,
+            value: (amount * 127.0).round().clamp(0.0, 127.0) as u8,
+        };
+
+        let message = match *command {
+            Command::NoteOn { note, velocity } => MidiMessage::NoteOn { channel, note, velocity: velocity.clamp(1, 127) },
+            Command::NoteOff { note } => MidiMessage::NoteOff { channel, note, velocity: 64 },
+            Command::AllNotesOff => MidiMessage::ControlChange { channel, controller: CC_ALL_NOTES_OFF, value: 0 },
+            Command::Panic => MidiMessage::ControlChange { channel, controller: CC_ALL_SOUND_OFF, value: 0 },
+            Command::PitchBend(bend) => MidiMessage::PitchBend {
+                channel,
+                value: (bend * 8192.0).round().clamp(-8192.0, 8191.0) as i16,
+            },
+            Command::ControlChange { controller, value } => MidiMessage::ControlChange { channel, controller, value },
+            Command::SetModWheel(amount) => control(CC_MOD_WHEEL, amount),
+            Command::SetSustain(sustain) => control(CC_SUSTAIN, if sustain { 1.0 } else { 0.0 }),
+            Command::SetMasterVolume(volume) => control(CC_VOLUME, volume),
+            Command::SetPan(pan) => control(CC_PAN, (pan + 1.0) * 64.0 / 127.0),
+            Command::SetDetune(detune) => control(CC_DETUNE, detune),
+            Command::SetFilterCutoff(cutoff) => control(CC_CUTOFF, control_from_cutoff(cutoff)),
+            Command::SetFilterResonance(resonance) => control(CC_RESONANCE, resonance),
+            Command::SetWaveform(waveform) => MidiMessage::ProgramChange { channel, program: waveform.index() as u8 },
+            _ => return None,
+        };
+        Some(message)
+    }
+
+    // Encodes the message as raw MIDI bytes, always with a status byte
+    pub fn to_bytes(&self) -> ([u8; 3], usize) {
+        match *self {
+            MidiMessage::NoteOff { channel, note, velocity } => ([0x80 | channel, note, velocity], 3),
+            MidiMessage::NoteOn { channel, note, velocity } => ([0x90 | channel, note, velocity], 3),
+            MidiMessage::PolyPressure { channel, note, pressure } => ([0xA0 | channel, note, pressure], 3),
+            MidiMessage::ControlChange { channel, controller, value } => ([0xB0 | channel, controller, value], 3),
+            MidiMessage::ProgramChange { channel, program } => ([0xC0 | channel, program, 0], 2),
+            MidiMessage::ChannelPressure { channel, pressure } => ([0xD0 | channel, pressure, 0], 2),
+            MidiMessage::PitchBend { channel, value } => {
+                let raw = (value as i32 + 8192).clamp(0, 16383) as u16;
+                ([0xE0 | channel, (raw & 0x7F) as u8, (raw >> 7) as u8], 3)
+            }
+        }
+    }
+}
@@ -0,0 +1,13 @@
+pub mod message;
+pub mod parser;
+pub mod recorder;
+pub mod smf;
+pub mod transport;
+
+pub use message::MidiMessage;
+pub use parser::MidiParser;
+pub use recorder::MidiRecorder;
+pub use smf::{MidiFile, SmfError};
+pub use transport::MidiReader;
+#[cfg(all(target_os = "linux", feature = "alsa"))]
+pub use transport::AlsaSeqPort;
@@ -0,0 +1,115 @@
+use super::message::MidiMessage;
+
+// Incremental MIDI 1.0 byte stream parser. Handles running status, real-time
+// bytes interleaved with other messages, and skips system exclusive and system
+// common messages, which the synth has no use for.
+#[derive(Debug, Clone, Default)]
+pub struct MidiParser {
+    status:     Option<u8>,
+    data:       [u8; 2],
+    len:        usize,
+    in_sysex:   bool,
+    // Data bytes still to skip for a system common message
+    skip:       usize,
+}
+
+impl MidiParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Feeds one byte, returning a message when it completes one
+    pub fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        match byte {
+            // Real-time messages may appear anywhere, even mid-message, and change nothing
+            0xF8..=0xFF => None,
+            0xF0 => {
+                self.in_sysex = true;
+                self.clear();
+                None
+            }
+            0xF7 => {
+                self.in_sysex = false;
+                None
+            }
+            0xF1..=0xF6 => {
+                // System common cancels running status
+                self.in_sysex = false;
+                self.clear();
+                self.skip = match byte {
+                    0xF1 | 0xF3 => 1,
+                    0xF2 => 2,
+                    _ => 0,
+                };
+                None
+            }
+            0x80..=0xEF => {
+                self.in_sysex = false;
+                self.skip = 0;
+                self.status = Some(byte);
+                self.len = 0;
+                None
+            }
+            _ => self.push_data(byte),
+        }
+    }
+
+    // Feeds a buffer of bytes, calling `on_message` for every message completed
+    pub fn feed(&mut self, bytes: &[u8], mut on_message: impl FnMut(MidiMessage)) {
+        for &byte in bytes {
+            if let Some(message) = self.push(byte) {
+                on_message(message);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.status = None;
+        self.len = 0;
+        self.skip = 0;
+    }
+
+    fn push_data(&mut self, byte: u8) -> Option<MidiMessage> {
+        if self.in_sysex {
+            return None;
+        }
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+        // Data without a status byte, e.g. when joining a stream midway
+        let status = self.status?;
+
+        self.data[self.len] = byte;
+        self.len += 1;
+        if self.len < data_length(status) {
+            return None;
+        }
+        // Running status: later data bytes reuse the same status
+        self.len = 0;
+
+        let channel = status & 0x0F;
+        let [first, second] = self.data;
+        let message = match status & 0xF0 {
+            0x80 => MidiMessage::NoteOff { channel, note: first, velocity: second },
+            0x90 if second == 0 => MidiMessage::NoteOff { channel, note: first, velocity: 64 },
+            0x90 => MidiMessage::NoteOn { channel, note: first, velocity: second },
+            0xA0 => MidiMessage::PolyPressure { channel, note: first, pressure: second },
+            0xB0 => MidiMessage::ControlChange { channel, controller: first, value: second },
+            0xC0 => MidiMessage::ProgramChange { channel, program: first },
+            0xD0 => MidiMessage::ChannelPressure { channel, pressure: first },
+            _ => {
+                let raw = (second as i16) << 7 | first as i16;
+                MidiMessage::PitchBend { channel, value: raw - 8192 }
+            }
+        };
+        Some(message)
+    }
+}
+
+fn data_length(status: u8) -> usize {
+    match status & 0xF0 {
+        0xC0 | 0xD0 => 1,
+        _ => 2,
+    }
+}
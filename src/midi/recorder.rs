@@ -0,0 +1,82 @@
+use super::message::MidiMessage;
+use super::smf::{Division, MidiFile, TrackEvent, TrackEventKind, DEFAULT_TEMPO};
+use crate::synth::synth::CC_SUSTAIN;
+use crate::synth::Command;
+
+// Resolution of recorded files; at the default 120 bpm one tick is about a millisecond
+pub const RECORD_TICKS_PER_QUARTER: u16 = 480;
+
+// Collects a live performance as timestamped MIDI messages and turns it into a
+// format 0 Standard MIDI File. Times are seconds from the start of the recording.
+#[derive(Debug, Clone, Default)]
+pub struct MidiRecorder {
+    events:     Vec<(f64, MidiMessage)>,
+    held:       Vec<(u8, u8)>, // (channel, note)
+    sustained:  Vec<u8>,       // Channels with the pedal down
+}
+
+impl MidiRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, time: f64, message: MidiMessage) {
+        match message {
+            MidiMessage::NoteOn { channel, note, .. } => self.held.push((channel, note)),
+            MidiMessage::NoteOff { channel, note, .. } => self.held.retain(|held| *held != (channel, note)),
+            MidiMessage::ControlChange { channel, controller: CC_SUSTAIN, value } => {
+                self.sustained.retain(|held| *held != channel);
+                if value >= 64 {
+                    self.sustained.push(channel);
+                }
+            }
+            _ => {}
+        }
+        self.events.push((time.max(0.0), message));
+    }
+
+    // Records a synth command, if it has a MIDI equivalent
+    pub fn record_command(&mut self, time: f64, command: &Command) -> bool {
+        match MidiMessage::from_command(command) {
+            Some(message) => {
+                self.record(time, message);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    // Ends the recording at `time`, releasing anything still held so the file
+    // never leaves notes hanging
+    pub fn finish(mut self, time: f64) -> MidiFile {
+        for (channel, note) in std::mem::take(&mut self.held) {
+            self.events.push((time, MidiMessage::NoteOff { channel, note, velocity: 64 }));
+        }
+        for channel in std::mem::take(&mut self.sustained) {
+            self.events.push((time, MidiMessage::ControlChange { channel, controller: CC_SUSTAIN, value: 0 }));
+        }
+
+        let ticks_per_second = RECORD_TICKS_PER_QUARTER as f64 * 1e6 / DEFAULT_TEMPO as f64;
+        let mut track = vec![TrackEvent { tick: 0, kind: TrackEventKind::Tempo(DEFAULT_TEMPO) }];
+        track.extend(self.events.iter().map(|(time, message)| TrackEvent {
+            tick: (time * ticks_per_second).round() as u64,
+            kind: TrackEventKind::Midi(*message),
+        }));
+        // Messages can arrive from several threads with slightly out-of-order times
+        track.sort_by_key(|event| event.tick);
+
+        MidiFile {
+            format: 0,
+            division: Division::TicksPerQuarter(RECORD_TICKS_PER_QUARTER),
+            tracks: vec![track],
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use super::message::MidiMessage;
+use super::parser::MidiParser;
+use crate::render::TimedEvent;
+
+// Tempo until the first tempo event, in microseconds per quarter note (120 bpm)
+pub const DEFAULT_TEMPO: u32 = 500_000;
+
+#[derive(Debug)]
+pub enum SmfError {
+    Io(io::Error),
+    Format(String),
+}
+
+impl fmt::Display for SmfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmfError::Io(e) => write!(f, "{}", e),
+            SmfError::Format(message) => write!(f, "not a valid MIDI file: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SmfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SmfError::Io(e) => Some(e),
+            SmfError::Format(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for SmfError {
+    fn from(error: io::Error) -> Self {
+        SmfError::Io(error)
+    }
+}
+
+fn format_error<T>(message: &str) -> Result<T, SmfError> {
+    Err(SmfError::Format(message.to_string()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Division {
+    TicksPerQuarter(u16),
+    // Ticks are fractions of SMPTE frames, independent of tempo
+    Smpte { frames_per_second: u8, ticks_per_frame: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackEventKind {
+    Midi(MidiMessage),
+    // Microseconds per quarter note
+    Tempo(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackEvent {
+    pub tick:   u64, // Absolute, from the start of the track
+    pub kind:   TrackEventKind,
+}
+
+// A Standard MIDI File, format 0 or 1. Only the events the synth can use are
+// kept: channel messages and tempo changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiFile {
+    pub format:     u16,
+    pub division:   Division,
+    pub tracks:     Vec<Vec<TrackEvent>>,
+}
+
+impl MidiFile {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SmfError> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, SmfError> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        let (id, header) = reader.chunk()?;
+        if id != *b"MThd" || header.len() < 6 {
+            return format_error("missing MThd header");
+        }
+        let format = u16::from_be_bytes([header[0], header[1]]);
+        let track_count = u16::from_be_bytes([header[2], header[3]]);
+        let division = match i16::from_be_bytes([header[4], header[5]]) {
+            0 => return format_error("division of zero ticks"),
+            ticks if ticks > 0 => Division::TicksPerQuarter(ticks as u16),
+            smpte => Division::Smpte {
+                frames_per_second: (-(smpte >> 8)) as u8,
+                ticks_per_frame: (smpte & 0xFF) as u8,
+            },
+        };
+        if format > 1 {
+            return Err(SmfError::Format(format!("format {} is not supported, only 0 and 1", format)));
+        }
+
+        let mut tracks = Vec::with_capacity(track_count as usize);
+        while tracks.len() < track_count as usize {
+            let (id, data) = reader.chunk()?;
+            // Unknown chunk types must be skipped
+            if id == *b"MTrk" {
+                tracks.push(parse_track(data)?);
+            }
+        }
+
+        Ok(MidiFile { format, division, tracks })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    // Encodes the file without running status, which every reader accepts
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let division = match self.division {
+            Division::TicksPerQuarter(ticks) => ticks.min(0x7FFF),
+            Division::Smpte { frames_per_second, ticks_per_frame } => {
+                u16::from_be_bytes([(frames_per_second as i8).wrapping_neg() as u8, ticks_per_frame])
+            }
+        };
+
+        let mut bytes = Vec::new();
+        let mut header = Vec::new();
+        header.extend_from_slice(&self.format.to_be_bytes());
+        header.extend_from_slice(&(self.tracks.len() as u16).to_be_bytes());
+        header.extend_from_slice(&division.to_be_bytes());
+        write_chunk(&mut bytes, b"MThd", &header);
+
+        for track in &self.tracks {
+            let mut data = Vec::new();
+            let mut last_tick = 0;
+            for event in track {
+                write_variable_length(&mut data, (event.tick.saturating_sub(last_tick)).min(0x0FFF_FFFF) as u32);
+                last_tick = last_tick.max(event.tick);
+                match event.kind {
+                    TrackEventKind::Midi(message) => {
+                        let (message, len) = message.to_bytes();
+                        data.extend_from_slice(&message[..len]);
+                    }
+                    TrackEventKind::Tempo(tempo) => {
+                        data.extend_from_slice(&[0xFF, 0x51, 0x03]);
+                        data.extend_from_slice(&tempo.to_be_bytes()[1..]);
+                    }
+                }
+            }
+            data.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+            write_chunk(&mut bytes, b"MTrk", &data);
+        }
+        bytes
+    }
+
+    pub fn tempo_map(&self) -> TempoMap {
+        let mut changes: Vec<(u64, u32)> = self.tracks.iter()
+            .flatten()
+            .filter_map(|event| match event.kind {
+                TrackEventKind::Tempo(tempo) => Some((event.tick, tempo)),
+                _ => None,
+            })
+            .collect();
+        changes.sort_by_key(|(tick, _)| *tick);
+        TempoMap { division: self.division, changes }
+    }
+
+    // Every track merged into one list of synth commands timed in seconds, ready
+    // for offline rendering or real-time playback. Events on the same tick keep
+    // their order in the file.
+    pub fn timed_events(&self) -> Vec<TimedEvent> {
+        let tempo_map = self.tempo_map();
+
+        let mut merged: Vec<(u64, usize, usize, MidiMessage)> = Vec::new();
+        for (track_index, track) in self.tracks.iter().enumerate() {
+            for (event_index, event) in track.iter().enumerate() {
+                if let TrackEventKind::Midi(message) = event.kind {
+                    merged.push((event.tick, track_index, event_index, message));
+                }
+            }
+        }
+        merged.sort_by_key(|(tick, track, index, _)| (*tick, *track, *index));
+
+        merged.into_iter()
+            .filter_map(|(tick, _, _, message)| {
+                let command = message.to_command()?;
+                Some(TimedEvent::new(tempo_map.seconds_at(tick), command))
+            })
+            .collect()
+    }
+}
+
+// Converts ticks to seconds, following every tempo change in the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoMap {
+    division:   Division,
+    changes:    Vec<(u64, u32)>,
+}
+
+impl TempoMap {
+    pub fn seconds_at(&self, tick: u64) -> f64 {
+        let ticks_per_quarter = match self.division {
+            Division::TicksPerQuarter(ticks) => ticks as f64,
+            Division::Smpte { frames_per_second, ticks_per_frame } => {
+                // 29 stands for 29.97 drop-frame
+                let fps = if frames_per_second == 29 { 29.97 } else { frames_per_second as f64 };
+                return tick as f64 / (fps * ticks_per_frame.max(1) as f64);
+            }
+        };
+
+        let mut seconds = 0.0;
+        let mut last_tick = 0;
+        let mut tempo = DEFAULT_TEMPO;
+        for &(change_tick, change_tempo) in self.changes.iter().take_while(|(change_tick, _)| *change_tick < tick) {
+            seconds += (change_tick - last_tick) as f64 * tempo as f64 / 1e6 / ticks_per_quarter;
+            last_tick = change_tick;
+            tempo = change_tempo;
+        }
+        seconds + (tick - last_tick) as f64 * tempo as f64 / 1e6 / ticks_per_quarter
+    }
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(id);
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(data);
+}
+
+fn write_variable_length(bytes: &mut Vec<u8>, value: u32) {
+    let mut groups = [0u8; 4];
+    let mut count = 0;
+    let mut value = value;
+    loop {
+        groups[count] = (value & 0x7F) as u8;
+        count += 1;
+        value >>= 7;
+        if value == 0 {
+            break;
+        }
+    }
+    for index in (0..count).rev() {
+        let more = if index > 0 { 0x80 } else { 0 };
+        bytes.push(groups[index] | more);
+    }
+}
+
+fn parse_track(data: &[u8]) -> Result<Vec<TrackEvent>, SmfError> {
+    let mut reader = Reader { bytes: data, position: 0 };
+    let mut parser = MidiParser::new();
+    let mut events = Vec::new();
+    let mut tick = 0u64;
+    let mut running_status = None;
+
+    while !reader.is_empty() {
+        tick += reader.variable_length()? as u64;
+
+        let status = match reader.peek()? {
+            byte if byte & 0x80 != 0 => {
+                reader.byte()?;
+                byte
+            }
+            _ => running_status.ok_or_else(|| SmfError::Format("data byte without a status".to_string()))?,
+        };
+
+        match status {
+            0xFF => {
+                running_status = None;
+                let kind = reader.byte()?;
+                let length = reader.variable_length()? as usize;
+                let meta = reader.take(length)?;
+                match kind {
+                    0x2F => break, // End of track
+                    0x51 if meta.len() == 3 => {
+                        let tempo = u32::from_be_bytes([0, meta[0], meta[1], meta[2]]);
+                        events.push(TrackEvent { tick, kind: TrackEventKind::Tempo(tempo.max(1)) });
+                    }
+                    _ => {}
+                }
+            }
+            0xF0 | 0xF7 => {
+                running_status = None;
+                let length = reader.variable_length()? as usize;
+                reader.take(length)?;
+            }
+            0x80..=0xEF => {
+                running_status = Some(status);
+                let data_length = if matches!(status & 0xF0, 0xC0 | 0xD0) { 1 } else { 2 };
+                parser.push(status);
+                let mut message = None;
+                for &byte in reader.take(data_length)? {
+                    message = parser.push(byte & 0x7F);
+                }
+                if let Some(message) = message {
+                    events.push(TrackEvent { tick, kind: TrackEventKind::Midi(message) });
+                }
+            }
+            _ => return Err(SmfError::Format(format!("unexpected status byte {:#04x}", status))),
+        }
+    }
+
+    Ok(events)
+}
+
+struct Reader<'a> {
+    bytes:      &'a [u8],
+    position:   usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn peek(&self) -> Result<u8, SmfError> {
+        match self.bytes.get(self.position) {
+            Some(byte) => Ok(*byte),
+            None => format_error("unexpected end of data"),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, SmfError> {
+        let byte = self.peek()?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], SmfError> {
+        let end = self.position.checked_add(length).filter(|end| *end <= self.bytes.len());
+        let Some(end) = end else {
+            return format_error("unexpected end of data");
+        };
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    // Up to four bytes, seven bits each, most significant first
+    fn variable_length(&mut self) -> Result<u32, SmfError> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        format_error("variable-length quantity longer than four bytes")
+    }
+
+    fn chunk(&mut self) -> Result<([u8; 4], &'a [u8]), SmfError> {
+        let header = self.take(8)?;
+        let id = [header[0], header[1], header[2], header[3]];
+        let length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        // Some writers get the length of the last chunk wrong; take what is there
+        let length = length.min(self.bytes.len() - self.position);
+        Ok((id, self.take(length)?))
+    }
+}
@@ -0,0 +1,135 @@
+use std::io::{self, Read};
+use super::message::MidiMessage;
+use super::parser::MidiParser;
+
+// Reads MIDI messages from any byte transport: a raw MIDI device such as
+// /dev/snd/midiC1D0, a pipe, a recorded file, an in-memory slice in tests, or an
+// ALSA sequencer port
+pub struct MidiReader<R> {
+    transport:  R,
+    parser:     MidiParser,
+    buffer:     [u8; 256],
+    filled:     usize,
+    position:   usize,
+}
+
+impl<R: Read> MidiReader<R> {
+    pub fn new(transport: R) -> Self {
+        MidiReader {
+            transport,
+            parser: MidiParser::new(),
+            buffer: [0; 256],
+            filled: 0,
+            position: 0,
+        }
+    }
+
+    // Blocks until the next message arrives; None once the transport is closed
+    pub fn next_message(&mut self) -> io::Result<Option<MidiMessage>> {
+        loop {
+            while self.position < self.filled {
+                let byte = self.buffer[self.position];
+                self.position += 1;
+                if let Some(message) = self.parser.push(byte) {
+                    return Ok(Some(message));
+                }
+            }
+
+            self.position = 0;
+            self.filled = match self.transport.read(&mut self.buffer) {
+                Ok(0) => return Ok(None),
+                Ok(filled) => filled,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => 0,
+                Err(e) => return Err(e),
+            };
+        }
+    }
+}
+
+impl<R: Read> Iterator for MidiReader<R> {
+    type Item = io::Result<MidiMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_message().transpose()
+    }
+}
+
+// A virtual ALSA sequencer port other applications can connect to, e.g. with
+// `aconnect`. Sequencer events are turned back into MIDI bytes so they go through
+// the same parser as every other transport.
+#[cfg(all(target_os = "linux", feature = "alsa"))]
+pub struct AlsaSeqPort {
+    seq:    alsa::seq::Seq,
+    port:   i32,
+}
+
+#[cfg(all(target_os = "linux", feature = "alsa"))]
+impl AlsaSeqPort {
+    pub fn open(client_name: &str, port_name: &str) -> alsa::Result<Self> {
+        use alsa::seq::{PortCap, PortType, Seq};
+        use std::ffi::CString;
+
+        let seq = Seq::open(None, Some(alsa::Direction::Capture), false)?;
+        let client_name = CString::new(client_name).unwrap_or_default();
+        let port_name = CString::new(port_name).unwrap_or_default();
+        seq.set_client_name(&client_name)?;
+        let port = seq.create_simple_port(
+            &port_name,
+            PortCap::WRITE | PortCap::SUBS_WRITE,
+            PortType::MIDI_GENERIC | PortType::APPLICATION,
+        )?;
+        Ok(AlsaSeqPort { seq, port })
+    }
+
+    // "client:port", as accepted by `aconnect`
+    pub fn address(&self) -> String {
+        let client = self.seq.client_id().unwrap_or(-1);
+        format!("{}:{}", client, self.port)
+    }
+
+    fn next_message(&self) -> alsa::Result<MidiMessage> {
+        use alsa::seq::{EvCtrl, EvNote, EventType};
+
+        let mut input = self.seq.input();
+        loop {
+            let event = input.event_input()?;
+            let note = event.get_data::<EvNote>();
+            let ctrl = event.get_data::<EvCtrl>();
+            let data = |value: i32| value.clamp(0, 127) as u8;
+
+            let message = match (event.get_type(), note, ctrl) {
+                (EventType::Noteon, Some(n), _) => MidiMessage::NoteOn { channel: n.channel & 0x0F, note: n.note, velocity: n.velocity },
+                (EventType::Noteoff, Some(n), _) => MidiMessage::NoteOff { channel: n.channel & 0x0F, note: n.note, velocity: n.velocity },
+                (EventType::Keypress, Some(n), _) => MidiMessage::PolyPressure { channel: n.channel & 0x0F, note: n.note, pressure: n.velocity },
+                (EventType::Controller, _, Some(c)) => MidiMessage::ControlChange {
+                    channel: c.channel & 0x0F,
+                    controller: data(c.param as i32),
+                    value: data(c.value),
+                },
+                (EventType::Pgmchange, _, Some(c)) => MidiMessage::ProgramChange { channel: c.channel & 0x0F, program: data(c.value) },
+                (EventType::Chanpress, _, Some(c)) => MidiMessage::ChannelPressure { channel: c.channel & 0x0F, pressure: data(c.value) },
+                (EventType::Pitchbend, _, Some(c)) => MidiMessage::PitchBend {
+                    channel: c.channel & 0x0F,
+                    value: c.value.clamp(-8192, 8191) as i16,
+                },
+                // Port subscriptions, clock and the like
+                _ => continue,
+            };
+            return Ok(message);
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "alsa"))]
+impl Read for AlsaSeqPort {
+    // Yields one complete message per read
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.len() < 3 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "buffer too small for a MIDI message"));
+        }
+        let message = self.next_message().map_err(io::Error::other)?;
+        let (bytes, len) = message.to_bytes();
+        buf[..len].copy_from_slice(&bytes[..len]);
+        Ok(len)
+    }
+}
@@ -0,0 +1,51 @@
+use std::fs::File;
+use std::io::Read;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use pulsar::midi::{MidiMessage, MidiReader};
+use crate::error::PulsarError;
+
+// MIDI messages arriving on a background thread, for the input loop to forward
+// to the synth alongside the computer keyboard
+pub struct MidiInput {
+    pub name:       String,
+    pub messages:   Receiver<MidiMessage>,
+}
+
+// `source` is either `alsa`, for a virtual sequencer port, or the path of a raw
+// MIDI device or pipe. The reader thread is never joined: it may be blocked in a
+// read when Pulsar quits, and it ends by itself once the receiver is gone.
+pub fn open(source: &str) -> Result<MidiInput, PulsarError> {
+    let (transport, name) = open_transport(source)?;
+    let (sender, messages) = mpsc::channel();
+
+    thread::Builder::new()
+        .name("midi_input".to_string())
+        .spawn(move || {
+            for message in MidiReader::new(transport) {
+                let Ok(message) = message else { break };
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        })
+        .map_err(|e| PulsarError::Midi(e.to_string()))?;
+
+    Ok(MidiInput { name, messages })
+}
+
+fn open_transport(source: &str) -> Result<(Box<dyn Read + Send>, String), PulsarError> {
+    #[cfg(all(target_os = "linux", feature = "alsa"))]
+    if source == "alsa" {
+        let port = pulsar::midi::AlsaSeqPort::open("Pulsar", "Pulsar MIDI In")
+            .map_err(|e| PulsarError::Midi(format!("cannot create ALSA sequencer port: {}", e)))?;
+        let name = format!("ALSA port {}", port.address());
+        return Ok((Box::new(port), name));
+    }
+
+    let file = File::open(source).map_err(|source_error| PulsarError::Io {
+        path: source.to_string(),
+        source: source_error,
+    })?;
+    Ok((Box::new(file), source.to_string()))
+}
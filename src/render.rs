@@ -0,0 +1,182 @@
+use crate::synth::{Command, Synth, CHANNELS};
+use crate::synth::waveform::WaveForm;
+use std::fmt;
+use std::path::Path;
+
+// Upper bound on how long release tails are rendered after the last event
+const MAX_TAIL_SECONDS: f64 = 30.0;
+const BLOCK_SIZE: usize = 256; // Frames per rendered block
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimedEvent {
+    pub time:       f64, // Seconds from the start of the render
+    pub command:    Command,
+}
+
+impl TimedEvent {
+    pub fn new(time: f64, command: Command) -> Self {
+        TimedEvent { time, command }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    Int16,
+    Int24,
+    Float32,
+}
+
+impl SampleFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "16" => Some(SampleFormat::Int16),
+            "24" => Some(SampleFormat::Int24),
+            "32" | "32f" | "float" => Some(SampleFormat::Float32),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseEventError {
+    pub line:       usize,
+    pub message:    String,
+}
+
+impl fmt::Display for ParseEventError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseEventError {}
+
+// Parses a plain-text event list, one event per line:
+//
+//     # time(s)  event
+//     0.0        on 60 100
+//     0.5        off 60
+//     0.5        detune 0.3
+//     0.5        volume 0.8
+//     1.0        waveform saw
+pub fn parse_events(text: &str) -> Result<Vec<TimedEvent>, ParseEventError> {
+    let mut events = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message: &str| ParseEventError { line: line_number, message: message.to_string() };
+
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let number = |i: usize| -> Result<f64, ParseEventError> {
+            fields.get(i)
+                .ok_or_else(|| error("missing argument"))?
+                .parse::<f64>()
+                .map_err(|_| error(&format!("invalid number `{}`", fields[i])))
+        };
+        let midi_value = |i: usize| -> Result<u8, ParseEventError> {
+            let value = number(i)?;
+            if (0.0..=127.0).contains(&value) && value.fract() == 0.0 {
+                Ok(value as u8)
+            } else {
+                Err(error(&format!("`{}` is not in 0..=127", fields[i])))
+            }
+        };
+
+        let time = number(0)?;
+        if time < 0.0 {
+            return Err(error("event time cannot be negative"));
+        }
+
+        let command = match fields.get(1).copied() {
+            Some("on") => Command::NoteOn {
+                note: midi_value(2)?,
+                velocity: if fields.len() > 3 { midi_value(3)? } else { 127 },
+            },
+            Some("off") => Command::NoteOff { note: midi_value(2)? },
+            Some("detune") => Command::SetDetune(number(2)? as f32),
+            Some("volume") => Command::SetMasterVolume(number(2)? as f32),
+            Some("waveform") => {
+                let name = fields.get(2).ok_or_else(|| error("missing argument"))?;
+                Command::SetWaveform(WaveForm::from_name(name).ok_or_else(|| error(&format!("unknown waveform `{}`", name)))?)
+            }
+            Some(other) => return Err(error(&format!("unknown event `{}`", other))),
+            None => return Err(error("missing event")),
+        };
+
+        events.push(TimedEvent::new(time, command));
+    }
+
+    events.sort_by(|a, b| a.time.total_cmp(&b.time));
+    Ok(events)
+}
+
+// Renders the events through the synth with sample-accurate timing, splitting
+// blocks at event boundaries. Rendering continues after the last event until
+// every voice has finished its release. The result is interleaved stereo.
+pub fn render(synth: &mut Synth, events: &[TimedEvent]) -> Vec<f32> {
+    let sample_rate = synth.sample_rate as f64;
+    let to_samples = |seconds: f64| (seconds * sample_rate).round() as u64;
+
+    let last_event = events.iter().map(|event| to_samples(event.time)).max().unwrap_or(0);
+    let max_length = last_event + to_samples(MAX_TAIL_SECONDS);
+
+    let mut output = Vec::with_capacity(last_event as usize * CHANNELS);
+    let mut block = [0.0; BLOCK_SIZE * CHANNELS];
+    let mut pending = events.iter().peekable();
+    let mut position = 0u64;
+
+    loop {
+        while let Some(event) = pending.next_if(|event| to_samples(event.time) <= position) {
+            synth.apply(event.command);
+        }
+
+        let next_event = pending.peek().map(|event| to_samples(event.time));
+        if position >= max_length || (next_event.is_none() && synth.active_voice_count() == 0) {
+            break;
+        }
+
+        let until = next_event.unwrap_or(max_length).min(max_length);
+        let frames = ((until - position) as usize).min(BLOCK_SIZE);
+        synth.process(&mut block[..frames * CHANNELS]);
+        output.extend_from_slice(&block[..frames * CHANNELS]);
+        position += frames as u64;
+    }
+
+    output
+}
+
+pub fn write_wav<P: AsRef<Path>>(
+    path: P,
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    format: SampleFormat,
+) -> Result<(), hound::Error> {
+    let (bits_per_sample, sample_format) = match format {
+        SampleFormat::Int16 => (16, hound::SampleFormat::Int),
+        SampleFormat::Int24 => (24, hound::SampleFormat::Int),
+        SampleFormat::Float32 => (32, hound::SampleFormat::Float),
+    };
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample,
+        sample_format,
+    };
+
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for &sample in samples {
+        let sample = sample.clamp(-1.0, 1.0);
+        match format {
+            SampleFormat::Int16 => writer.write_sample((sample * i16::MAX as f32) as i16)?,
+            SampleFormat::Int24 => writer.write_sample((sample * 8_388_607.0) as i32)?,
+            SampleFormat::Float32 => writer.write_sample(sample)?,
+        }
+    }
+    writer.finalize()
+}
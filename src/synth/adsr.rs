@@ -1,7 +1,8 @@
 use std::time::Duration;
 use crate::synth::envelope::EnvelopeStage;
 
-#[derive(Debug, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ADSR {
     pub attack: Duration,
     pub decay: Duration,
@@ -19,34 +20,48 @@ impl ADSR {
         }
     }
 
-    // This method calculates the amplitude based on the current time and stage
-    pub fn calculate_amplitude(&self, stage: EnvelopeStage, elapsed: Duration, start_amplitude: f32) -> f32 {
+    // Length of a timed stage in samples; Sustain and Finished last until something else happens
+    pub fn stage_samples(&self, stage: EnvelopeStage, sample_rate: f32) -> Option<u64> {
+        let duration = match stage {
+            EnvelopeStage::Attack => self.attack,
+            EnvelopeStage::Decay => self.decay,
+            EnvelopeStage::Release => self.release,
+            EnvelopeStage::Sustain | EnvelopeStage::Finished => return None,
+        };
+        Some((duration.as_secs_f64() * sample_rate as f64).round() as u64)
+    }
+
+    // This method calculates the amplitude based on the number of samples spent in the stage
+    pub fn calculate_amplitude(&self, stage: EnvelopeStage, elapsed_samples: u64, sample_rate: f32, start_amplitude: f32) -> f32 {
+        let progress = |length: u64| elapsed_samples as f32 / length as f32;
+
         match stage {
             EnvelopeStage::Attack => {
-                if elapsed >= self.attack {
+                let length = self.stage_samples(stage, sample_rate).unwrap_or(0);
+                if elapsed_samples >= length {
                     1.0 // Full amplitude at the end of attack
                 } else {
-                    elapsed.as_secs_f32() / self.attack.as_secs_f32() // Linear ramp up
+                    progress(length) // Linear ramp up
                 }
             }
             EnvelopeStage::Decay => {
-                if elapsed >= self.decay {
+                let length = self.stage_samples(stage, sample_rate).unwrap_or(0);
+                if elapsed_samples >= length {
                     self.sustain // Hold at sustain level
                 } else {
-                    let decay_progress = elapsed.as_secs_f32() / self.decay.as_secs_f32();
-                    1.0 - decay_progress * (1.0 - self.sustain) // Ramp down to sustain
+                    1.0 - progress(length) * (1.0 - self.sustain) // Ramp down to sustain
                 }
             }
             EnvelopeStage::Sustain => self.sustain, // Constant sustain level
             EnvelopeStage::Release => {
-                if elapsed >= self.release {
+                let length = self.stage_samples(stage, sample_rate).unwrap_or(0);
+                if elapsed_samples >= length {
                     0.0 // End of release
                 } else {
-                    let release_progress = elapsed.as_secs_f32() / self.release.as_secs_f32();
-                    start_amplitude * (1.0 - release_progress) // Ramp down from current amplitude
+                    start_amplitude * (1.0 - progress(length)) // Ramp down from current amplitude
                 }
             }
             EnvelopeStage::Finished => 0.0,
         }
     }
-}
\ No newline at end of file
+}
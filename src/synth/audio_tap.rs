@@ -0,0 +1,75 @@
+use super::spsc::{self, Consumer, Producer};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Default)]
+struct TapState {
+    active:     AtomicBool,
+    dropped:    AtomicU64, // Samples lost because the reader fell behind
+}
+
+// Audio-thread end of a tap on the rendered output. Copying a block in never
+// blocks or allocates; if the reader has fallen behind, the whole block is
+// dropped so the channels stay interleaved.
+pub struct AudioTap {
+    samples:    Producer<f32>,
+    state:      Arc<TapState>,
+}
+
+// The other end, read on a thread that is free to block, e.g. to write a file
+pub struct TapReader {
+    samples:    Consumer<f32>,
+    state:      Arc<TapState>,
+}
+
+// `capacity` is in samples and bounds how far the reader may lag behind the audio
+pub fn audio_tap(capacity: usize) -> (AudioTap, TapReader) {
+    let (producer, consumer) = spsc::channel(capacity);
+    let state = Arc::new(TapState::default());
+    (
+        AudioTap { samples: producer, state: Arc::clone(&state) },
+        TapReader { samples: consumer, state },
+    )
+}
+
+impl AudioTap {
+    // Does nothing unless the reader has started the tap
+    pub fn write(&mut self, block: &[f32]) {
+        if !self.state.active.load(Ordering::Acquire) {
+            return;
+        }
+        if self.samples.free_slots() < block.len() {
+            self.state.dropped.fetch_add(block.len() as u64, Ordering::Relaxed);
+            return;
+        }
+        for &sample in block {
+            let _ = self.samples.push(sample);
+        }
+    }
+}
+
+impl TapReader {
+    // Discards anything left over from an earlier run, then lets the audio thread write
+    pub fn start(&mut self) {
+        while self.samples.pop().is_some() {}
+        self.state.dropped.store(0, Ordering::Relaxed);
+        self.state.active.store(true, Ordering::Release);
+    }
+
+    // Blocks already copied in can still be read afterwards
+    pub fn stop(&mut self) {
+        self.state.active.store(false, Ordering::Release);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state.active.load(Ordering::Acquire)
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.state.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn read(&mut self) -> Option<f32> {
+        self.samples.pop()
+    }
+}
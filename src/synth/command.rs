@@ -0,0 +1,83 @@
+use super::adsr::ADSR;
+use super::filter::{FilterMode, FilterModel};
+use super::lfo::LfoSettings;
+use super::patch::{ModSlot, Patch};
+use super::synth::{CC_ALL_NOTES_OFF, CC_ALL_SOUND_OFF, CC_SUSTAIN};
+use super::voice_pool::StealPolicy;
+use super::waveform::{Quality, WaveForm};
+
+// Messages sent from the input/UI thread to the audio thread
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    NoteOn { note: u8, velocity: u8 },
+    NoteOff { note: u8 },
+    // Releases every note, like MIDI CC 123
+    AllNotesOff,
+    // Silences every voice at once, like MIDI CC 120
+    Panic,
+    SetDetune(f32),
+    SetMasterVolume(f32),
+    SetPan(f32),
+    SetStereoWidth(f32),
+    SetWaveform(WaveForm),
+    ToggleWaveform,
+    SetQuality(Quality),
+    ToggleQuality,
+    SetMaxPolyphony(usize),
+    SetStealPolicy(StealPolicy),
+    // -1 to 1, scaled by the bend range
+    PitchBend(f32),
+    SetPitchBendRange(f32),
+    SetModWheel(f32),
+    SetSustain(bool),
+    // MIDI control change, mapped onto the commands above where there is a standard meaning
+    ControlChange { controller: u8, value: u8 },
+    SetFilterEnabled(bool),
+    SetFilterMode(FilterMode),
+    SetFilterModel(FilterModel),
+    SetFilterCutoff(f32),
+    SetFilterResonance(f32),
+    SetFilterKeyTracking(f32),
+    // Octaves, may be negative
+    SetFilterEnvelopeAmount(f32),
+    SetFilterEnvelope(ADSR),
+    SetLfo { index: usize, settings: LfoSettings },
+    // Beats per minute
+    SetTempo(f32),
+    // None clears the slot
+    SetModSlot { index: usize, slot: Option<ModSlot> },
+}
+
+impl Command {
+    // Whether dropping this command would leave notes sounding
+    pub fn ends_notes(&self) -> bool {
+        match *self {
+            Command::NoteOff { .. } | Command::AllNotesOff | Command::Panic | Command::SetSustain(false) => true,
+            Command::ControlChange { controller, value } => match controller {
+                CC_SUSTAIN => value < 64,
+                CC_ALL_SOUND_OFF | CC_ALL_NOTES_OFF => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+// Parameter state published by the audio thread for the UI to read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SynthSnapshot {
+    pub detune:             f32,
+    pub master_volume:      f32,
+    pub pan:                f32,
+    pub stereo_width:       f32,
+    pub waveform:           WaveForm,
+    pub quality:            Quality,
+    pub active_voices:      usize,
+    pub max_polyphony:      usize,
+    pub steal_policy:       StealPolicy,
+    pub pitch_bend:         f32,
+    pub mod_wheel:          f32,
+    pub sustain:            bool,
+    pub patch:              Patch,
+    pub tempo:              f32,
+}
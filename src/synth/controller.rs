@@ -0,0 +1,203 @@
+use super::adsr::ADSR;
+use super::command::{Command, SynthSnapshot};
+use super::filter::{FilterMode, FilterModel};
+use super::lfo::LfoSettings;
+use super::patch::ModSlot;
+use super::spsc::Producer;
+use super::triple_buffer::Output;
+use super::voice_pool::StealPolicy;
+use super::waveform::{Quality, WaveForm};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long a note off waits for room in a full queue before it is given up on
+const RELEASE_SEND_TIMEOUT: Duration = Duration::from_millis(250);
+
+// Shared flag asking the audio thread to fade out and end the source. Unlike a
+// queued command it cannot be dropped when the queue is full.
+#[derive(Debug, Clone, Default)]
+pub struct StopSignal(Arc<AtomicBool>);
+
+impl StopSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+// UI-side handle to a synth running on the audio thread. Commands that do not fit
+// in the queue are dropped, except ones that end notes: those wait for the audio
+// thread to make room, since losing one would leave a note hanging.
+pub struct SynthController {
+    commands:       Producer<Command>,
+    snapshots:      Output<SynthSnapshot>,
+    stop:           StopSignal,
+}
+
+impl SynthController {
+    pub fn new(commands: Producer<Command>, snapshots: Output<SynthSnapshot>, stop: StopSignal) -> Self {
+        SynthController {
+            commands,
+            snapshots,
+            stop,
+        }
+    }
+
+    pub fn send(&mut self, command: Command) -> bool {
+        let deadline = Instant::now() + RELEASE_SEND_TIMEOUT;
+        loop {
+            if self.commands.push(command).is_ok() {
+                return true;
+            }
+            if !command.ends_notes() || Instant::now() >= deadline {
+                return false;
+            }
+            // The audio thread drains the queue every block, so a full queue clears quickly
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    pub fn note_on(&mut self, note: u8, velocity: u8) -> bool {
+        self.send(Command::NoteOn { note, velocity })
+    }
+
+    pub fn note_off(&mut self, note: u8) -> bool {
+        self.send(Command::NoteOff { note })
+    }
+
+    pub fn all_notes_off(&mut self) -> bool {
+        self.send(Command::AllNotesOff)
+    }
+
+    pub fn panic(&mut self) -> bool {
+        self.send(Command::Panic)
+    }
+
+    pub fn set_detune(&mut self, detune: f32) -> bool {
+        self.send(Command::SetDetune(detune))
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) -> bool {
+        self.send(Command::SetMasterVolume(volume))
+    }
+
+    pub fn set_pan(&mut self, pan: f32) -> bool {
+        self.send(Command::SetPan(pan))
+    }
+
+    pub fn set_stereo_width(&mut self, width: f32) -> bool {
+        self.send(Command::SetStereoWidth(width))
+    }
+
+    pub fn set_waveform(&mut self, waveform: WaveForm) -> bool {
+        self.send(Command::SetWaveform(waveform))
+    }
+
+    pub fn toggle_waveform(&mut self) -> bool {
+        self.send(Command::ToggleWaveform)
+    }
+
+    pub fn set_quality(&mut self, quality: Quality) -> bool {
+        self.send(Command::SetQuality(quality))
+    }
+
+    pub fn toggle_quality(&mut self) -> bool {
+        self.send(Command::ToggleQuality)
+    }
+
+    pub fn set_max_polyphony(&mut self, max_polyphony: usize) -> bool {
+        self.send(Command::SetMaxPolyphony(max_polyphony))
+    }
+
+    pub fn set_steal_policy(&mut self, policy: StealPolicy) -> bool {
+        self.send(Command::SetStealPolicy(policy))
+    }
+
+    pub fn pitch_bend(&mut self, bend: f32) -> bool {
+        self.send(Command::PitchBend(bend))
+    }
+
+    pub fn set_pitch_bend_range(&mut self, semitones: f32) -> bool {
+        self.send(Command::SetPitchBendRange(semitones))
+    }
+
+    pub fn set_mod_wheel(&mut self, amount: f32) -> bool {
+        self.send(Command::SetModWheel(amount))
+    }
+
+    pub fn set_sustain(&mut self, sustain: bool) -> bool {
+        self.send(Command::SetSustain(sustain))
+    }
+
+    pub fn control_change(&mut self, controller: u8, value: u8) -> bool {
+        self.send(Command::ControlChange { controller, value })
+    }
+
+    pub fn set_filter_enabled(&mut self, enabled: bool) -> bool {
+        self.send(Command::SetFilterEnabled(enabled))
+    }
+
+    pub fn set_filter_mode(&mut self, mode: FilterMode) -> bool {
+        self.send(Command::SetFilterMode(mode))
+    }
+
+    pub fn set_filter_model(&mut self, model: FilterModel) -> bool {
+        self.send(Command::SetFilterModel(model))
+    }
+
+    pub fn set_filter_cutoff(&mut self, cutoff: f32) -> bool {
+        self.send(Command::SetFilterCutoff(cutoff))
+    }
+
+    pub fn set_filter_resonance(&mut self, resonance: f32) -> bool {
+        self.send(Command::SetFilterResonance(resonance))
+    }
+
+    pub fn set_filter_key_tracking(&mut self, amount: f32) -> bool {
+        self.send(Command::SetFilterKeyTracking(amount))
+    }
+
+    pub fn set_filter_envelope_amount(&mut self, octaves: f32) -> bool {
+        self.send(Command::SetFilterEnvelopeAmount(octaves))
+    }
+
+    pub fn set_filter_envelope(&mut self, adsr: ADSR) -> bool {
+        self.send(Command::SetFilterEnvelope(adsr))
+    }
+
+    pub fn set_lfo(&mut self, index: usize, settings: LfoSettings) -> bool {
+        self.send(Command::SetLfo { index, settings })
+    }
+
+    pub fn set_tempo(&mut self, tempo: f32) -> bool {
+        self.send(Command::SetTempo(tempo))
+    }
+
+    pub fn set_mod_slot(&mut self, index: usize, slot: Option<ModSlot>) -> bool {
+        self.send(Command::SetModSlot { index, slot })
+    }
+
+    // Fades the output to silence, after which the source ends
+    pub fn stop(&self) {
+        self.stop.request();
+    }
+
+    // For threads that need to know a shutdown is in progress, e.g. to bound how long they wait
+    pub fn stop_signal(&self) -> StopSignal {
+        self.stop.clone()
+    }
+
+    // Latest parameter state published by the audio thread
+    pub fn snapshot(&mut self) -> SynthSnapshot {
+        *self.snapshots.read()
+    }
+}
@@ -1,5 +1,4 @@
 use crate::synth::adsr::ADSR;
-use std::time::Instant;
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum EnvelopeStage {
@@ -10,56 +9,58 @@ pub enum EnvelopeStage {
     Finished,
 }
 
+impl EnvelopeStage {
+    fn next(self) -> Self {
+        match self {
+            EnvelopeStage::Attack => EnvelopeStage::Decay,
+            EnvelopeStage::Decay => EnvelopeStage::Sustain,
+            EnvelopeStage::Sustain => EnvelopeStage::Sustain,
+            EnvelopeStage::Release | EnvelopeStage::Finished => EnvelopeStage::Finished,
+        }
+    }
+}
+
+// Advances one step per rendered sample, so the shape only depends on the
+// sample rate and never on when the audio callback happens to run.
+#[derive(Debug, Clone, Copy)]
 pub struct Envelope {
     pub adsr: ADSR,
     pub stage: EnvelopeStage,
-    pub start_time: Instant,
+    pub sample_rate: f32,
+    pub elapsed_samples: u64,
     pub amplitude: f32,
     pub release_start_amplitude: f32,
 }
 
 impl Envelope {
-    pub fn new(adsr: ADSR) -> Self {
+    pub fn new(adsr: ADSR, sample_rate: f32) -> Self {
         Envelope {
             adsr,
             stage: EnvelopeStage::Attack,  // Start with Attack stage
-            start_time: Instant::now(),
+            sample_rate,
+            elapsed_samples: 0,
             amplitude: 0.0,  // Start at zero amplitude
             release_start_amplitude: 0.0,
         }
     }
 
-    pub fn update(&mut self) {
-        let elapsed = Instant::now().duration_since(self.start_time);
-        
-        self.amplitude = match self.stage {
-            EnvelopeStage::Attack => {
-                let amp = self.adsr.calculate_amplitude(self.stage, elapsed, 0.0);
-                if elapsed >= self.adsr.attack {
-                    self.stage = EnvelopeStage::Decay;
-                    self.start_time = Instant::now();
-                }
-                amp
-            }
-            EnvelopeStage::Decay => {
-                let amp = self.adsr.calculate_amplitude(self.stage, elapsed, 0.0);
-                if elapsed >= self.adsr.decay {
-                    self.stage = EnvelopeStage::Sustain;
-                }
-                amp
-            }
-            EnvelopeStage::Sustain => {
-                self.adsr.calculate_amplitude(self.stage, elapsed, 0.0)
-            }
-            EnvelopeStage::Release => {
-                let amp = self.adsr.calculate_amplitude(self.stage, elapsed, self.release_start_amplitude);
-                if elapsed >= self.adsr.release {
-                    self.stage = EnvelopeStage::Finished;
-                }
-                amp
+    pub fn next_sample(&mut self) -> f32 {
+        self.amplitude = self.adsr.calculate_amplitude(
+            self.stage,
+            self.elapsed_samples,
+            self.sample_rate,
+            self.release_start_amplitude,
+        );
+        self.elapsed_samples += 1;
+
+        if let Some(length) = self.adsr.stage_samples(self.stage, self.sample_rate) {
+            if self.elapsed_samples >= length {
+                self.stage = self.stage.next();
+                self.elapsed_samples = 0;
             }
-            EnvelopeStage::Finished => 0.0,
-        };
+        }
+
+        self.amplitude
     }
 
     pub fn is_finished(&self) -> bool {
@@ -68,15 +69,15 @@ impl Envelope {
 
     pub fn trigger_attack(&mut self) {
         self.stage = EnvelopeStage::Attack;
-        self.start_time = Instant::now();
+        self.elapsed_samples = 0;
         self.amplitude = 0.0;
     }
 
     pub fn trigger_release(&mut self) {
         if self.stage != EnvelopeStage::Release && self.stage != EnvelopeStage::Finished {
             self.stage = EnvelopeStage::Release;
-            self.start_time = Instant::now();
+            self.elapsed_samples = 0;
             self.release_start_amplitude = self.amplitude;
         }
     }
-}
\ No newline at end of file
+}
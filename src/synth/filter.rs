@@ -0,0 +1,200 @@
+use super::adsr::ADSR;
+use super::synth::CHANNELS;
+use std::f32::consts::PI;
+
+pub const MIN_CUTOFF: f32 = 20.0;
+pub const MAX_CUTOFF: f32 = 20000.0;
+
+// Key tracking is relative to this note, so at 100% C4 plays at the set cutoff
+const KEY_TRACKING_CENTER: f32 = 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+}
+
+impl FilterMode {
+    pub const ALL: [FilterMode; 4] = [FilterMode::LowPass, FilterMode::HighPass, FilterMode::BandPass, FilterMode::Notch];
+
+    pub fn toggle(&mut self) {
+        *self = match self {
+            FilterMode::LowPass => FilterMode::HighPass,
+            FilterMode::HighPass => FilterMode::BandPass,
+            FilterMode::BandPass => FilterMode::Notch,
+            FilterMode::Notch => FilterMode::LowPass,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterModel {
+    // 12 dB/octave state-variable filter; clean, stays stable when modulated quickly
+    StateVariable,
+    // 24 dB/octave transistor ladder with a saturating input, self-oscillates near full resonance
+    Ladder,
+}
+
+// The synth-wide filter patch. Each voice runs its own filter and cutoff envelope from it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilterSettings {
+    pub enabled:            bool,
+    pub mode:               FilterMode,
+    pub model:              FilterModel,
+    pub cutoff:             f32, // Hz
+    pub resonance:          f32, // 0 to 1
+    pub key_tracking:       f32, // 0 keeps the cutoff fixed, 1 moves it with the note's pitch
+    pub envelope_amount:    f32, // Octaves the envelope moves the cutoff at full level; may be negative
+    pub envelope:           ADSR,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        FilterSettings {
+            enabled:            false,
+            mode:               FilterMode::LowPass,
+            model:              FilterModel::StateVariable,
+            cutoff:             MAX_CUTOFF,
+            resonance:          0.0,
+            key_tracking:       0.0,
+            envelope_amount:    0.0,
+            envelope:           ADSR::new(10, 300, 0.0, 300),
+        }
+    }
+}
+
+impl FilterSettings {
+    // Cutoff for `note` with the cutoff envelope at `envelope_level`
+    pub fn cutoff_for(&self, note: u8, envelope_level: f32) -> f32 {
+        let octaves = self.key_tracking * (note as f32 - KEY_TRACKING_CENTER) / 12.0
+            + self.envelope_amount * envelope_level;
+        (self.cutoff * octaves.exp2()).clamp(MIN_CUTOFF, MAX_CUTOFF)
+    }
+}
+
+// Filter state for one voice, both channels. Coefficients are only recomputed
+// by `set`, which voices call at control rate rather than every sample.
+#[derive(Debug, Clone, Copy)]
+pub struct Filter {
+    mode:       FilterMode,
+    model:      FilterModel,
+    // State-variable coefficients (Zavalishin/Simper trapezoidal form)
+    k:          f32,
+    a1:         f32,
+    a2:         f32,
+    a3:         f32,
+    // Ladder coefficients: one-pole gain and feedback
+    pole:       f32,
+    feedback:   f32,
+    state:      [[f32; 5]; CHANNELS],
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::new()
+    }
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Filter {
+            mode: FilterMode::LowPass,
+            model: FilterModel::StateVariable,
+            k: 2.0,
+            a1: 0.0,
+            a2: 0.0,
+            a3: 0.0,
+            pole: 0.0,
+            feedback: 0.0,
+            state: [[0.0; 5]; CHANNELS],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.state = [[0.0; 5]; CHANNELS];
+    }
+
+    pub fn set(&mut self, mode: FilterMode, model: FilterModel, cutoff: f32, resonance: f32, sample_rate: f32) {
+        self.mode = mode;
+        self.model = model;
+
+        // Kept below Nyquist, where the prewarped gain blows up
+        let cutoff = cutoff.clamp(MIN_CUTOFF, MAX_CUTOFF.min(sample_rate * 0.49));
+        let resonance = resonance.clamp(0.0, 1.0);
+        let g = (PI * cutoff / sample_rate).tan();
+
+        match model {
+            FilterModel::StateVariable => {
+                // Damping from 2 (no resonance) down to a Q of about 25
+                self.k = 2.0 - 1.96 * resonance;
+                self.a1 = 1.0 / (1.0 + g * (g + self.k));
+                self.a2 = g * self.a1;
+                self.a3 = g * self.a2;
+            }
+            FilterModel::Ladder => {
+                self.pole = g / (1.0 + g);
+                self.feedback = 4.0 * resonance;
+            }
+        }
+    }
+
+    pub fn process(&mut self, channel: usize, input: f32) -> f32 {
+        match self.model {
+            FilterModel::StateVariable => self.process_svf(channel, input),
+            FilterModel::Ladder => self.process_ladder(channel, input),
+        }
+    }
+
+    fn process_svf(&mut self, channel: usize, input: f32) -> f32 {
+        let state = &mut self.state[channel];
+        let (ic1, ic2) = (state[0], state[1]);
+
+        let v3 = input - ic2;
+        let band = self.a1 * ic1 + self.a2 * v3;
+        let low = ic2 + self.a2 * ic1 + self.a3 * v3;
+        state[0] = 2.0 * band - ic1;
+        state[1] = 2.0 * low - ic2;
+
+        let high = input - self.k * band - low;
+        match self.mode {
+            FilterMode::LowPass => low,
+            FilterMode::HighPass => high,
+            // Scaled so the peak stays at unity gain whatever the resonance
+            FilterMode::BandPass => band * self.k,
+            FilterMode::Notch => low + high,
+        }
+    }
+
+    // Four one-pole stages with the last one fed back to the input, which is
+    // soft-saturated to keep self-oscillation bounded. Modes other than lowpass
+    // are mixed from the stage outputs.
+    fn process_ladder(&mut self, channel: usize, input: f32) -> f32 {
+        let state = &mut self.state[channel];
+
+        // Lowpass makes up the passband level resonance takes away
+        let drive = if self.mode == FilterMode::LowPass { 1.0 + self.feedback } else { 1.0 };
+        let u = (input * drive - self.feedback * state[4]).tanh();
+
+        let mut stages = [0.0; 4];
+        let mut x = u;
+        for (stage, stage_state) in stages.iter_mut().zip(state.iter_mut()) {
+            let v = (x - *stage_state) * self.pole;
+            let y = v + *stage_state;
+            *stage_state = y + v;
+            *stage = y;
+            x = y;
+        }
+        state[4] = stages[3];
+
+        let [y1, y2, y3, y4] = stages;
+        let band = 4.0 * (y2 - 2.0 * y3 + y4);
+        match self.mode {
+            FilterMode::LowPass => y4,
+            FilterMode::HighPass => u - 4.0 * y1 + 6.0 * y2 - 4.0 * y3 + y4,
+            FilterMode::BandPass => band,
+            FilterMode::Notch => u - band,
+        }
+    }
+}
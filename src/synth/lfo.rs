@@ -0,0 +1,104 @@
+use super::waveform::{white_noise, WaveForm};
+
+// Number of LFOs in a patch
+pub const LFO_COUNT: usize = 2;
+
+pub const DEFAULT_TEMPO: f32 = 120.0; // Beats per minute
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LfoRate {
+    Hertz(f32),
+    // Length of one cycle in beats at the synth's tempo: 1.0 is a quarter note, 4.0 a bar of 4/4
+    Beats(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoRetrigger {
+    // One free-running cycle that every voice follows
+    Global,
+    // Each note restarts the cycle from the phase offset
+    PerVoice,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoDestination {
+    Pitch,
+    Amplitude,
+    PulseWidth,
+    Pan,
+    Cutoff,
+}
+
+// What `depth` means depends on the destination:
+//   Pitch       semitones either side
+//   Amplitude   0 to 1, how far the level dips at the bottom of the cycle
+//   PulseWidth  0 to 1, 1 sweeping from nearly silent to a square
+//   Pan         -1 to 1, added to the voice's position
+//   Cutoff      octaves either side
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LfoSettings {
+    pub shape:          WaveForm,
+    pub rate:           LfoRate,
+    pub phase:          f32, // Offset into the cycle, 0 to 1
+    pub retrigger:      LfoRetrigger,
+    pub destination:    LfoDestination,
+    pub depth:          f32, // 0 turns the LFO off
+}
+
+impl Default for LfoSettings {
+    fn default() -> Self {
+        LfoSettings {
+            shape:          WaveForm::Sine,
+            rate:           LfoRate::Hertz(5.0),
+            phase:          0.0,
+            retrigger:      LfoRetrigger::PerVoice,
+            destination:    LfoDestination::Pitch,
+            depth:          0.0,
+        }
+    }
+}
+
+impl LfoSettings {
+    pub fn frequency(&self, tempo: f32) -> f32 {
+        match self.rate {
+            LfoRate::Hertz(hertz) => hertz.max(0.0),
+            LfoRate::Beats(beats) => tempo / 60.0 / beats.max(1.0 / 64.0),
+        }
+    }
+}
+
+// Where an LFO is in its cycle. White noise becomes sample-and-hold: a new
+// random level at the start of every cycle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LfoState {
+    pub phase:  f32,
+    held:       f32,
+    seed:       u32,
+}
+
+impl LfoState {
+    // `seed` must be non-zero
+    pub fn new(seed: u32) -> Self {
+        let mut state = LfoState { phase: 0.0, held: 0.0, seed: seed.max(1) };
+        state.held = white_noise(&mut state.seed);
+        state
+    }
+
+    // Moves on by `cycles`, which may be more than one
+    pub fn advance(&mut self, cycles: f32) {
+        self.phase += cycles;
+        if self.phase >= 1.0 {
+            self.phase = self.phase.fract();
+            self.held = white_noise(&mut self.seed);
+        }
+    }
+
+    // From -1 to 1
+    pub fn value(&self, settings: &LfoSettings) -> f32 {
+        let phase = (self.phase + settings.phase).rem_euclid(1.0);
+        match settings.shape {
+            WaveForm::WhiteNoise => self.held,
+            shape => shape.generate(phase),
+        }
+    }
+}
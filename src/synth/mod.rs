@@ -1,12 +1,25 @@
 pub mod synth;
+pub mod audio_tap;
 pub mod synth_source;
 pub mod waveform;
-pub mod detune_slider;
 pub mod envelope;
+pub mod filter;
+pub mod lfo;
 pub mod adsr;
-pub mod oscillator;
-pub mod key_mapping;
+pub mod oscillator_bank;
+pub mod patch;
+pub mod note;
+pub mod command;
+pub mod controller;
+pub mod spsc;
+pub mod triple_buffer;
+pub mod voice;
+pub mod voice_pool;
 
 pub use synth::*;
 pub use synth_source::*;
-pub use detune_slider::*;
\ No newline at end of file
+pub use voice::VoiceId;
+pub use voice_pool::StealPolicy;
+pub use audio_tap::{audio_tap, AudioTap, TapReader};
+pub use command::{Command, SynthSnapshot};
+pub use controller::{StopSignal, SynthController};
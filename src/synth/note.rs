@@ -0,0 +1,87 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Note {
+    C = 0,
+    CSharp = 1,
+    D = 2,
+    DSharp = 3,
+    E = 4,
+    F = 5,
+    FSharp = 6,
+    G = 7,
+    GSharp = 8,
+    A = 9,
+    ASharp = 10,
+    B = 11,
+}
+
+impl Note {
+    pub const ALL: [Note; 12] = [
+        Note::C, Note::CSharp, Note::D, Note::DSharp, Note::E, Note::F,
+        Note::FSharp, Note::G, Note::GSharp, Note::A, Note::ASharp, Note::B,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PitchClass {
+    pub note: Note,
+    pub octave: i32,
+}
+
+impl PitchClass {
+    pub fn new(note: Note, octave: i32) -> Self {
+        PitchClass {
+            note,
+            octave,
+        }
+    }
+
+    // MIDI convention: note 60 is C4, note 69 is A4
+    pub fn from_midi(note: u8) -> Self {
+        PitchClass {
+            note: Note::ALL[(note % 12) as usize],
+            octave: (note / 12) as i32 - 1,
+        }
+    }
+
+    // Scientific pitch notation: "C4", "F#3", "Bb2", "C-1"
+    pub fn from_name(name: &str) -> Option<Self> {
+        let mut chars = name.chars();
+        let letter = match chars.next()?.to_ascii_uppercase() {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return None,
+        };
+        let rest = chars.as_str();
+        let (accidental, octave) = match rest.chars().next()? {
+            '#' => (1, &rest[1..]),
+            'b' => (-1, &rest[1..]),
+            _ => (0, rest),
+        };
+        let octave: i32 = octave.parse().ok()?;
+
+        let semitone: i32 = letter + accidental;
+        Some(PitchClass {
+            note: Note::ALL[semitone.rem_euclid(12) as usize],
+            octave: octave + semitone.div_euclid(12),
+        })
+    }
+
+    pub fn to_midi(&self) -> Option<u8> {
+        let number = (self.octave + 1) * 12 + self.note as i32;
+        u8::try_from(number).ok().filter(|n| *n < 128)
+    }
+
+    pub fn frequency(&self) -> f32 {
+        let semitones_from_a4 = (self.octave - 4) * 12 + (self.note as i32 - Note::A as i32);
+        440.0 * 2f32.powf(semitones_from_a4 as f32 / 12.0)
+    }
+}
+
+pub fn midi_to_frequency(note: u8) -> f32 {
+    440.0 * 2f32.powf((note as f32 - 69.0) / 12.0)
+}
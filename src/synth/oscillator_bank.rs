@@ -0,0 +1,262 @@
+use super::waveform::{white_noise, Quality, WaveForm, PULSE_WIDTH};
+use std::f32::consts::{FRAC_PI_4, SQRT_2, TAU};
+use wide::{f32x8, CmpGt, CmpLt};
+
+// Number of oscillators computed together; one vector covers a full unison stack
+pub const LANES: usize = 8;
+
+// Pitch modulation is limited to this many octaves either side of the note
+const MAX_PITCH_OCTAVES: f32 = 8.0;
+
+// A voice's unison oscillators stored as structure-of-arrays, one SIMD lane per
+// oscillator. Every waveform is evaluated for all lanes at once; unused lanes
+// have zero gain so they never reach the output.
+#[derive(Debug, Clone, Copy)]
+pub struct OscillatorBank {
+    pub phases:         f32x8,
+    pub increments:     f32x8,
+    pub gains_left:     f32x8,
+    pub gains_right:    f32x8,
+    // Per-frame change while the gains ramp to new pans, for `ramp_remaining` frames
+    gain_steps_left:    f32x8,
+    gain_steps_right:   f32x8,
+    ramp_remaining:     u32,
+    pub frequencies:    [f32; LANES],
+    pub pans:           [f32; LANES],
+    // Multiplies every frequency, for pitch modulation on top of the voice's tuning
+    pub pitch_ratio:    f32,
+    // Per-lane frequency multipliers, for modulating the unison spread
+    pub lane_ratios:    [f32; LANES],
+    pub pulse_width:    f32,
+    pub count:          usize,
+    pub waveform:       WaveForm,
+    pub quality:        Quality,
+    pub sample_rate:    f32,
+    noise_state:        [u32; LANES],
+}
+
+impl OscillatorBank {
+    pub fn new(waveform: WaveForm, quality: Quality, sample_rate: f32) -> Self {
+        OscillatorBank {
+            phases: f32x8::ZERO,
+            increments: f32x8::ZERO,
+            gains_left: f32x8::ZERO,
+            gains_right: f32x8::ZERO,
+            gain_steps_left: f32x8::ZERO,
+            gain_steps_right: f32x8::ZERO,
+            ramp_remaining: 0,
+            frequencies: [0.0; LANES],
+            pans: [0.0; LANES],
+            pitch_ratio: 1.0,
+            lane_ratios: [1.0; LANES],
+            pulse_width: PULSE_WIDTH,
+            count: 0,
+            waveform,
+            quality,
+            sample_rate,
+            noise_state: std::array::from_fn(|lane| 0x9E37_79B9u32.wrapping_mul(lane as u32 + 1)),
+        }
+    }
+
+    // Restarts the bank with `frequencies.len()` oscillators, all at phase zero and centred
+    pub fn reset(&mut self, frequencies: &[f32], waveform: WaveForm, quality: Quality) {
+        self.count = frequencies.len().min(LANES);
+        self.waveform = waveform;
+        self.quality = quality;
+        self.phases = f32x8::ZERO;
+        self.frequencies = [0.0; LANES];
+        self.frequencies[..self.count].copy_from_slice(&frequencies[..self.count]);
+        self.pitch_ratio = 1.0;
+        self.lane_ratios = [1.0; LANES];
+        self.pulse_width = PULSE_WIDTH;
+        self.update_increments();
+        self.set_pans(&[0.0; LANES][..self.count]);
+    }
+
+    pub fn set_frequency(&mut self, lane: usize, frequency: f32) {
+        if lane < self.count {
+            self.frequencies[lane] = frequency;
+            self.update_increments();
+        }
+    }
+
+    pub fn set_frequencies(&mut self, frequencies: &[f32]) {
+        let count = self.count.min(frequencies.len());
+        self.frequencies[..count].copy_from_slice(&frequencies[..count]);
+        self.update_increments();
+    }
+
+    pub fn set_pitch_ratio(&mut self, ratio: f32) {
+        let range = MAX_PITCH_OCTAVES.exp2();
+        let ratio = ratio.clamp(1.0 / range, range);
+        if ratio != self.pitch_ratio {
+            self.pitch_ratio = ratio;
+            self.update_increments();
+        }
+    }
+
+    pub fn set_lane_ratios(&mut self, ratios: &[f32; LANES]) {
+        if *ratios != self.lane_ratios {
+            self.lane_ratios = *ratios;
+            self.update_increments();
+        }
+    }
+
+    // Fraction of the cycle the pulse wave spends high, kept clear of the extremes where it vanishes
+    pub fn set_pulse_width(&mut self, width: f32) {
+        self.pulse_width = width.clamp(0.02, 0.98);
+    }
+
+    fn update_increments(&mut self) {
+        let step = self.pitch_ratio / self.sample_rate;
+        let increments: [f32; LANES] = std::array::from_fn(|lane| self.frequencies[lane] * self.lane_ratios[lane] * step);
+        self.increments = f32x8::from(increments);
+    }
+
+    // Equal-power pans in [-1, 1], with the unison normalization folded into the gains
+    pub fn set_pans(&mut self, pans: &[f32]) {
+        self.ramp_pans(pans, 0);
+    }
+
+    // Like `set_pans`, but moves the gains there linearly over `frames` frames so
+    // modulated pans don't step
+    pub fn ramp_pans(&mut self, pans: &[f32], frames: u32) {
+        let normalization = if self.count == 0 {
+            0.0
+        } else {
+            1.0 / ((self.count as f32).sqrt() * 1.5)
+        };
+
+        let mut left = [0.0; LANES];
+        let mut right = [0.0; LANES];
+        for lane in 0..self.count {
+            let pan = pans.get(lane).copied().unwrap_or(0.0).clamp(-1.0, 1.0);
+            let angle = (pan + 1.0) * FRAC_PI_4;
+            self.pans[lane] = pan;
+            left[lane] = angle.cos() * SQRT_2 * normalization;
+            right[lane] = angle.sin() * SQRT_2 * normalization;
+        }
+        let (left, right) = (f32x8::from(left), f32x8::from(right));
+
+        if frames == 0 {
+            self.gains_left = left;
+            self.gains_right = right;
+            self.gain_steps_left = f32x8::ZERO;
+            self.gain_steps_right = f32x8::ZERO;
+        } else {
+            let frames = f32x8::splat(frames as f32);
+            self.gain_steps_left = (left - self.gains_left) / frames;
+            self.gain_steps_right = (right - self.gains_right) / frames;
+        }
+        self.ramp_remaining = frames;
+    }
+
+    // Renders one stereo frame from every lane and advances the phases
+    pub fn next_frame(&mut self) -> (f32, f32) {
+        if self.count == 0 {
+            return (0.0, 0.0);
+        }
+
+        let samples = self.generate();
+
+        // An increment can pass a whole cycle at extreme pitches, so wrap by the floor
+        self.phases = wrap(self.phases + self.increments);
+
+        let frame = ((samples * self.gains_left).reduce_add(), (samples * self.gains_right).reduce_add());
+
+        if self.ramp_remaining > 0 {
+            self.gains_left += self.gain_steps_left;
+            self.gains_right += self.gain_steps_right;
+            self.ramp_remaining -= 1;
+        }
+        frame
+    }
+
+    fn generate(&mut self) -> f32x8 {
+        let phase = self.phases;
+        let one = f32x8::splat(1.0);
+        let two = f32x8::splat(2.0);
+        let four = f32x8::splat(4.0);
+        let half = f32x8::splat(0.5);
+        let band_limited = self.quality == Quality::BandLimited;
+        let dt = self.increments.abs().min(half);
+
+        match self.waveform {
+            WaveForm::Sine => (phase * f32x8::splat(TAU)).sin(),
+            WaveForm::Saw => {
+                let naive = phase.mul_sub(two, one);
+                if band_limited { naive - poly_blep(phase, dt) } else { naive }
+            }
+            WaveForm::Square => {
+                let naive = phase.cmp_lt(half).blend(one, -one);
+                if band_limited {
+                    naive + poly_blep(phase, dt) - poly_blep(wrap(phase + half), dt)
+                } else {
+                    naive
+                }
+            }
+            WaveForm::Pulse => {
+                let width = f32x8::splat(self.pulse_width);
+                let naive = phase.cmp_lt(width).blend(one, -one);
+                if band_limited {
+                    naive + poly_blep(phase, dt) - poly_blep(wrap(phase + one - width), dt)
+                } else {
+                    naive
+                }
+            }
+            WaveForm::Triangle => {
+                // Same shape as the scalar triangle: 0 at phase 0, peak at 0.25, trough at 0.75
+                let naive = (wrap(phase + f32x8::splat(0.75)) - half).abs().mul_sub(four, one);
+                if band_limited {
+                    let corner = f32x8::splat(8.0) * dt;
+                    naive - corner * poly_blamp(wrap(phase + f32x8::splat(0.75)), dt)
+                        + corner * poly_blamp(wrap(phase + f32x8::splat(0.25)), dt)
+                } else {
+                    naive
+                }
+            }
+            WaveForm::WhiteNoise => {
+                let mut noise = [0.0; LANES];
+                for (value, state) in noise.iter_mut().zip(self.noise_state.iter_mut()) {
+                    *value = white_noise(state);
+                }
+                f32x8::from(noise)
+            }
+        }
+    }
+}
+
+// Wraps any phase back into [0, 1) by subtracting its floor, built from `round`
+// since this version of `wide` has no `floor`
+fn wrap(x: f32x8) -> f32x8 {
+    let rounded = x.round();
+    let floor = rounded.cmp_gt(x).blend(rounded - f32x8::splat(1.0), rounded);
+    x - floor
+}
+
+// Residual between an ideal band-limited step and the naive one, for a
+// discontinuity at phase 0
+fn poly_blep(t: f32x8, dt: f32x8) -> f32x8 {
+    let one = f32x8::splat(1.0);
+    let two = f32x8::splat(2.0);
+
+    let x = t / dt;
+    let rising = two * x - x * x - one;
+    let y = (t - one) / dt;
+    let falling = y * y + two * y + one;
+
+    t.cmp_lt(dt).blend(rising, t.cmp_gt(one - dt).blend(falling, f32x8::ZERO))
+}
+
+// Integrated PolyBLEP, for a change of slope at phase 0
+fn poly_blamp(t: f32x8, dt: f32x8) -> f32x8 {
+    let one = f32x8::splat(1.0);
+    let third = f32x8::splat(1.0 / 3.0);
+
+    let x = t / dt - one;
+    let after = -(x * x * x) * third;
+    let y = (t - one) / dt + one;
+    let before = y * y * y * third;
+
+    t.cmp_lt(dt).blend(after, t.cmp_gt(one - dt).blend(before, f32x8::ZERO))
+}
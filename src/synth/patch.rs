@@ -0,0 +1,133 @@
+use super::filter::FilterSettings;
+use super::lfo::{LfoDestination, LfoSettings, LFO_COUNT};
+
+// Number of slots in the mod matrix
+pub const MOD_SLOTS: usize = 8;
+
+// Per-voice values a mod slot can read. Each is -1 to 1 or 0 to 1, as noted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModSource {
+    Lfo(usize),     // -1 to 1
+    AmpEnvelope,    // 0 to 1
+    FilterEnvelope, // 0 to 1
+    Velocity,       // 0 to 1
+    Key,            // -1 at the lowest MIDI note to 1 at the highest
+    ModWheel,       // 0 to 1
+    Random,         // -1 to 1, drawn once when the note starts
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModDestination {
+    Pitch,
+    Level,
+    PulseWidth,
+    Pan,
+    Cutoff,
+    Resonance,
+    Detune,
+    Attack,
+    Decay,
+    Release,
+}
+
+impl ModDestination {
+    pub const COUNT: usize = 10;
+
+    // What a slot with an amount of 1 moves the destination by at full source level:
+    // semitones for pitch, octaves for cutoff and envelope times (doubling or
+    // halving them), and the parameter's own units elsewhere
+    pub fn range(&self) -> f32 {
+        match self {
+            ModDestination::Pitch => 12.0,
+            ModDestination::Level => 1.0,
+            ModDestination::PulseWidth => 0.5,
+            ModDestination::Pan => 1.0,
+            ModDestination::Cutoff => 5.0,
+            ModDestination::Resonance => 1.0,
+            ModDestination::Detune => 1.0,
+            ModDestination::Attack | ModDestination::Decay | ModDestination::Release => 3.0,
+        }
+    }
+}
+
+// Connects a source to a destination; `amount` is from -1 to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModSlot {
+    pub source:         ModSource,
+    pub destination:    ModDestination,
+    pub amount:         f32,
+}
+
+// Source values for one voice at one control-rate update
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ModSources {
+    pub lfos:               [f32; LFO_COUNT],
+    pub amp_envelope:       f32,
+    pub filter_envelope:    f32,
+    pub velocity:           f32,
+    pub key:                f32,
+    pub mod_wheel:          f32,
+    pub random:             f32,
+}
+
+impl ModSources {
+    pub fn get(&self, source: ModSource) -> f32 {
+        match source {
+            ModSource::Lfo(index) => self.lfos.get(index).copied().unwrap_or(0.0),
+            ModSource::AmpEnvelope => self.amp_envelope,
+            ModSource::FilterEnvelope => self.filter_envelope,
+            ModSource::Velocity => self.velocity,
+            ModSource::Key => self.key,
+            ModSource::ModWheel => self.mod_wheel,
+            ModSource::Random => self.random,
+        }
+    }
+}
+
+// Offsets summed from every route into each destination, in the destination's units
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModTargets([f32; ModDestination::COUNT]);
+
+impl ModTargets {
+    pub fn get(&self, destination: ModDestination) -> f32 {
+        self.0[destination as usize]
+    }
+
+    pub fn add(&mut self, destination: ModDestination, offset: f32) {
+        self.0[destination as usize] += offset;
+    }
+}
+
+// The sound-shaping settings shared by every voice: the filter, the LFOs and the
+// mod matrix
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Patch {
+    pub filter:         FilterSettings,
+    pub lfos:           [LfoSettings; LFO_COUNT],
+    pub modulation:     [Option<ModSlot>; MOD_SLOTS],
+}
+
+impl Patch {
+    // Sums every LFO's own route and every mod slot into per-destination offsets
+    pub fn evaluate(&self, sources: &ModSources) -> ModTargets {
+        let mut targets = ModTargets::default();
+
+        for (lfo, value) in self.lfos.iter().zip(sources.lfos) {
+            let depth = lfo.depth;
+            match lfo.destination {
+                LfoDestination::Pitch => targets.add(ModDestination::Pitch, depth * value),
+                // Dips from full level at the top of the cycle
+                LfoDestination::Amplitude => targets.add(ModDestination::Level, -depth.clamp(0.0, 1.0) * (1.0 - value) * 0.5),
+                LfoDestination::PulseWidth => targets.add(ModDestination::PulseWidth, depth * value * 0.5),
+                LfoDestination::Pan => targets.add(ModDestination::Pan, depth * value),
+                LfoDestination::Cutoff => targets.add(ModDestination::Cutoff, depth * value),
+            }
+        }
+
+        for slot in self.modulation.iter().flatten() {
+            let amount = slot.amount.clamp(-1.0, 1.0);
+            targets.add(slot.destination, amount * sources.get(slot.source) * slot.destination.range());
+        }
+        targets
+    }
+}
@@ -1,74 +1,108 @@
-use super::waveform::WaveForm;
+use super::waveform::{Quality, WaveForm};
 use super::envelope::Envelope;
 use super::adsr::ADSR;
-use super::oscillator::Oscillator;
-use super::key_mapping::{Note, PitchClass, get_pitch_class};
+use super::filter::{FilterMode, FilterModel, MAX_CUTOFF, MIN_CUTOFF};
+use super::lfo::{LfoSettings, LfoState, DEFAULT_TEMPO, LFO_COUNT};
+use super::oscillator_bank::OscillatorBank;
+use super::note::midi_to_frequency;
+use super::patch::{ModSlot, Patch};
+use super::voice::{PendingNote, VoiceId, VoiceSettings, MAX_OSCILLATORS};
+use super::voice_pool::{Allocation, StealPolicy, VoicePool, DEFAULT_POLYPHONY};
+use super::command::{Command, SynthSnapshot};
+use crate::alloc_guard::RealtimeScope;
 
-use device_query::Keycode;
-use std::collections::{HashSet, HashMap};
-use lazy_static::lazy_static;
-use std::sync::atomic::{AtomicU64, Ordering};
-use wide::f32x4;
+// `process` renders interleaved left/right frames
+pub const CHANNELS: usize = 2;
+
+// Length of the fade applied to a stolen voice before its slot is reused
+const STEAL_FADE_MS: f32 = 5.0;
+
+pub const DEFAULT_PITCH_BEND_RANGE: f32 = 2.0; // Semitones
+
+// MIDI controllers with a fixed meaning; see `control_change`
+pub const CC_MOD_WHEEL: u8 = 1;
+pub const CC_VOLUME: u8 = 7;
+pub const CC_PAN: u8 = 10;
+pub const CC_SUSTAIN: u8 = 64;
+pub const CC_RESONANCE: u8 = 71; // "Harmonic content"
+pub const CC_CUTOFF: u8 = 74; // "Brightness"
+pub const CC_DETUNE: u8 = 94; // "Celeste (detune) depth"
+pub const CC_ALL_SOUND_OFF: u8 = 120;
+pub const CC_RESET_CONTROLLERS: u8 = 121;
+pub const CC_ALL_NOTES_OFF: u8 = 123;
 
 pub struct Synth {
     pub sample_rate:                f32,
-    pub sample_clock:               AtomicU64,
-    pub active_keys:                HashSet<Keycode>,
-    pub key_envelopes:              HashMap<Keycode, Envelope>,
-    pub oscillators:                HashMap<Keycode, Vec<Oscillator>>,
+    pub sample_clock:               u64,
+    pub voices:                     VoicePool,
     pub current_waveform:           WaveForm,
+    pub quality:                    Quality,
     pub adsr:                       ADSR,
     pub detune:                     f32,
     pub num_oscillators:            u32,
     pub master_volume:              f32,
+    pub pan:                        f32,
+    pub stereo_width:               f32,
+    pub pitch_bend:                 f32,
+    pub pitch_bend_range:           f32,
+    pub mod_wheel:                  f32,
+    pub sustain:                    bool,
+    pub patch:                      Patch,
+    // Free-running cycles that voices follow when an LFO is not retriggered per note
+    pub lfo_clocks:                 [LfoState; LFO_COUNT],
+    pub tempo:                      f32,
 }
 
 impl Synth {
     pub fn new(sample_rate: f32, adsr: ADSR) -> Self {
         Synth {
             sample_rate,
-            sample_clock:           AtomicU64::new(0),
-            active_keys:            HashSet::new(),
-            key_envelopes:          HashMap::new(),
-            oscillators:            HashMap::new(),
+            sample_clock:           0,
+            voices:                 VoicePool::new(
+                Envelope::new(adsr, sample_rate),
+                OscillatorBank::new(WaveForm::Sine, Quality::BandLimited, sample_rate),
+                DEFAULT_POLYPHONY,
+            ),
             current_waveform:       WaveForm::Sine,
+            quality:                Quality::BandLimited,
             adsr,                 
             detune:                 0.0,
             num_oscillators:        3,
             master_volume:          1.0,
+            pan:                    0.0,
+            stereo_width:           0.5,
+            pitch_bend:             0.0,
+            pitch_bend_range:       DEFAULT_PITCH_BEND_RANGE,
+            mod_wheel:              0.0,
+            sustain:                false,
+            patch:                  Patch::default(),
+            lfo_clocks:             std::array::from_fn(|index| LfoState::new(0x2545_F491 + index as u32)),
+            tempo:                  DEFAULT_TEMPO,
         }
     }
 
-    pub fn get_frequency(&self, key: Keycode) -> Option<f32> {
-        get_pitch_class(&key)
-            .and_then(|pitch_class| FREQUENCY_MAP.get(&pitch_class))
-            .cloned()
+    // Frequency of a MIDI note with the current pitch bend applied
+    pub fn get_frequency(&self, note: u8) -> f32 {
+        let bend = self.pitch_bend * self.pitch_bend_range;
+        midi_to_frequency(note) * 2f32.powf(bend / 12.0)
     }
 
-    pub fn get_detuned_frequencies(&self, base_freq: f32) -> Vec<f32> {
+    // Frequency of oscillator `index` out of `num_oscillators`, spread evenly across the detune range
+    pub fn get_detuned_frequency(&self, base_freq: f32, index: u32) -> f32 {
         if self.num_oscillators == 1 {
-            return vec![base_freq];
+            return base_freq;
         }
 
         let detune_factor = self.detune / 100.0;
         let detune_range = base_freq * detune_factor;
 
         let step = detune_range / (self.num_oscillators as f32 - 1.0).max(1.0);
-
-        (0..self.num_oscillators)
-            .map(|i| {
-                let offset = (i as f32 * step) - (detune_range / 2.0);
-                base_freq * (1.0 + offset / base_freq)
-            })
-            .collect()
-    }
-
-    pub fn increment_sample_clock(&self) {
-        self.sample_clock.fetch_add(1, Ordering::Relaxed);
+        let offset = (index as f32 * step) - (detune_range / 2.0);
+        base_freq * (1.0 + offset / base_freq)
     }
 
     pub fn get_polyphonic_scaling_factor(&self) -> f32 {
-        let num_active_keys = self.active_keys.len() as f32;
+        let num_active_keys = self.voices.held_count() as f32;
         if num_active_keys <= 1.0 {
             1.0
         } else {
@@ -78,69 +112,171 @@ impl Synth {
         }
     }
 
-    pub fn generate_waveform(&self, key: Keycode) -> f32 {
-        let t = self.get_sample_clock();
-    
-        if let Some(oscillators) = self.oscillators.get(&key) {
-            let mut waveform_value = 0.0;
-            let num_oscillators = oscillators.len();
-    
-            // SIMD processing for groups of 4 oscillators
-            for chunk in oscillators.chunks_exact(4) {
-                let samples = f32x4::from([
-                    chunk[0].next_sample(t),
-                    chunk[1].next_sample(t),
-                    chunk[2].next_sample(t),
-                    chunk[3].next_sample(t)
-                ]);
-                
-                // Process the entire SIMD vector at once and sum
-                waveform_value += samples.reduce_add();
-            }
-    
-            // Handle remaining oscillators
-            let remainder = oscillators.chunks_exact(4).remainder();
-            for osc in remainder {
-                waveform_value += osc.next_sample(t);
+    // Renders the next block of interleaved stereo output into `out`, one voice at a time.
+    // This is the single entry point used by the rodio source, offline rendering and plugin hosts.
+    pub fn process(&mut self, out: &mut [f32]) {
+        let _realtime = RealtimeScope::enter();
+        debug_assert!(out.len().is_multiple_of(CHANNELS), "process expects whole stereo frames");
+        out.fill(0.0);
+        let scaling_factor = self.get_polyphonic_scaling_factor();
+
+        for index in 0..self.voices.voices().len() {
+            let mut offset = 0;
+            while offset < out.len() && !self.voices.voices()[index].is_free() {
+                let settings = VoiceSettings {
+                    patch: &self.patch,
+                    tempo: self.tempo,
+                    stereo_width: self.stereo_width,
+                    detune: self.detune,
+                    mod_wheel: self.mod_wheel,
+                    lfo_clocks: &self.lfo_clocks,
+                    frame: offset / CHANNELS,
+                };
+                let voice = &mut self.voices.voices_mut()[index];
+                offset += voice.render(&mut out[offset..], &settings) * CHANNELS;
+
+                // A stolen voice has finished fading, so hand its slot to the waiting note
+                if let Some(pending) = voice.take_pending() {
+                    self.start_voice(index, pending);
+                }
             }
-    
-            // Improved normalization for cleaner chord sounds
-            let normalized_value = waveform_value / ((num_oscillators as f32).sqrt() * 1.5);
-    
-            let envelope_amplitude = self.key_envelopes.get(&key)
-                .map(|env| env.amplitude)
-                .unwrap_or(0.0);
-    
-            normalized_value * envelope_amplitude * self.master_volume
-        } else {
-            0.0
         }
+
+        let gain = self.master_volume * scaling_factor;
+        for sample in out.iter_mut() {
+            *sample = soft_clip(*sample * gain);
+        }
+
+        let frames = out.len() / CHANNELS;
+        for (clock, lfo) in self.lfo_clocks.iter_mut().zip(&self.patch.lfos) {
+            clock.advance(lfo.frequency(self.tempo) * frames as f32 / self.sample_rate);
+        }
+
+        self.sample_clock += frames as u64;
     }
 
     pub fn set_detune(&mut self, detune: f32) {
         self.detune = detune;
-        
-        let updates: Vec<(Keycode, Vec<f32>)> = self.oscillators.keys()
-            .filter_map(|&key| {
-                self.get_frequency(key).map(|base_freq| {
-                    let detuned_frequencies = self.get_detuned_frequencies(base_freq);
-                    (key, detuned_frequencies)
-                })
-            })
-            .collect();
-    
-        for (key, detuned_frequencies) in updates {
-            if let Some(oscillators) = self.oscillators.get_mut(&key) {
-                for (osc, &freq) in oscillators.iter_mut().zip(detuned_frequencies.iter()) {
-                    osc.set_frequency(freq);
-                }
+        self.update_frequencies();
+    }
+
+    // Bend from -1 to 1, scaled by `pitch_bend_range`; applies to sounding notes too
+    pub fn set_pitch_bend(&mut self, bend: f32) {
+        self.pitch_bend = bend.clamp(-1.0, 1.0);
+        self.update_frequencies();
+    }
+
+    pub fn set_pitch_bend_range(&mut self, semitones: f32) {
+        self.pitch_bend_range = semitones.clamp(0.0, 24.0);
+        self.update_frequencies();
+    }
+
+    // Stored for modulation routing, from 0 to 1
+    pub fn set_mod_wheel(&mut self, amount: f32) {
+        self.mod_wheel = amount.clamp(0.0, 1.0);
+    }
+
+    // While the pedal is down, note offs leave notes sounding; lifting it releases them
+    pub fn set_sustain(&mut self, sustain: bool) {
+        self.sustain = sustain;
+        if sustain {
+            return;
+        }
+
+        for voice in self.voices.voices_mut() {
+            if voice.sustained {
+                voice.release();
+            }
+        }
+    }
+
+    // Standard MIDI controllers; anything else is ignored
+    pub fn control_change(&mut self, controller: u8, value: u8) {
+        let amount = value.min(127) as f32 / 127.0;
+        match controller {
+            CC_MOD_WHEEL => self.set_mod_wheel(amount),
+            CC_VOLUME => self.set_master_volume(amount),
+            CC_PAN => self.set_pan(value.min(127) as f32 / 64.0 - 1.0),
+            CC_SUSTAIN => self.set_sustain(value >= 64),
+            CC_RESONANCE => self.set_filter_resonance(amount),
+            CC_CUTOFF => self.set_filter_cutoff(cutoff_from_control(amount)),
+            CC_DETUNE => self.set_detune(amount),
+            CC_ALL_SOUND_OFF => self.clear_voices(),
+            CC_RESET_CONTROLLERS => {
+                self.set_mod_wheel(0.0);
+                self.set_sustain(false);
+                self.set_pitch_bend(0.0);
             }
+            CC_ALL_NOTES_OFF => self.release_all(),
+            _ => {}
+        }
+    }
+
+    pub fn set_filter_enabled(&mut self, enabled: bool) {
+        self.patch.filter.enabled = enabled;
+    }
+
+    pub fn set_filter_mode(&mut self, mode: FilterMode) {
+        self.patch.filter.mode = mode;
+    }
+
+    pub fn set_filter_model(&mut self, model: FilterModel) {
+        self.patch.filter.model = model;
+    }
+
+    // Base cutoff in Hz, before key tracking and the cutoff envelope
+    pub fn set_filter_cutoff(&mut self, cutoff: f32) {
+        self.patch.filter.cutoff = cutoff.clamp(MIN_CUTOFF, MAX_CUTOFF);
+    }
+
+    pub fn set_filter_resonance(&mut self, resonance: f32) {
+        self.patch.filter.resonance = resonance.clamp(0.0, 1.0);
+    }
+
+    pub fn set_filter_key_tracking(&mut self, amount: f32) {
+        self.patch.filter.key_tracking = amount.clamp(0.0, 1.0);
+    }
+
+    // Octaves the cutoff envelope sweeps at its peak; negative values sweep down
+    pub fn set_filter_envelope_amount(&mut self, octaves: f32) {
+        self.patch.filter.envelope_amount = octaves.clamp(-10.0, 10.0);
+    }
+
+    // Like the amplitude ADSR, this shapes notes started from now on
+    pub fn set_filter_envelope(&mut self, adsr: ADSR) {
+        self.patch.filter.envelope = adsr;
+    }
+
+    // Out-of-range indices are ignored
+    pub fn set_lfo(&mut self, index: usize, settings: LfoSettings) {
+        if let Some(lfo) = self.patch.lfos.get_mut(index) {
+            *lfo = settings;
         }
     }
 
-    pub fn update_envelope(&mut self) {
-        for envelope in self.key_envelopes.values_mut() {
-            envelope.update();
+    // Sets or clears a mod matrix slot; out-of-range indices are ignored
+    pub fn set_mod_slot(&mut self, index: usize, slot: Option<ModSlot>) {
+        if let Some(existing) = self.patch.modulation.get_mut(index) {
+            *existing = slot.map(|slot| ModSlot { amount: slot.amount.clamp(-1.0, 1.0), ..slot });
+        }
+    }
+
+    // Replaces the filter, LFOs and mod matrix in one go; takes effect on sounding notes too
+    pub fn set_patch(&mut self, patch: Patch) {
+        self.patch = patch;
+    }
+
+    // Beats per minute, for LFOs synced to the beat
+    pub fn set_tempo(&mut self, tempo: f32) {
+        self.tempo = tempo.clamp(20.0, 400.0);
+    }
+
+    fn update_frequencies(&mut self) {
+        for index in 0..self.voices.voices().len() {
+            let base_freq = self.get_frequency(self.voices.voices()[index].note);
+            let frequencies: [f32; MAX_OSCILLATORS] = std::array::from_fn(|i| self.get_detuned_frequency(base_freq, i as u32));
+
+            self.voices.voices_mut()[index].oscillators.set_frequencies(&frequencies);
         }
     }
 
@@ -148,62 +284,240 @@ impl Synth {
         self.master_volume = volume.clamp(0.0, 1.0);
     }
 
-    pub fn add_note(&mut self, key: Keycode) {
-        if self.active_keys.insert(key) {
-            let mut new_envelope = Envelope::new(self.adsr);
-            new_envelope.trigger_attack();
-            self.key_envelopes.insert(key, new_envelope);
-    
-            if let Some(frequency) = self.get_frequency(key) {
-                let detuned_frequencies = self.get_detuned_frequencies(frequency);
-                let oscillators = detuned_frequencies
-                    .into_iter()
-                    .map(|freq| Oscillator::new(freq, self.current_waveform.clone()))
-                    .collect();
-                self.oscillators.insert(key, oscillators);
+    // Pan position given to new voices, from -1 (left) to 1 (right)
+    pub fn set_pan(&mut self, pan: f32) {
+        self.pan = pan.clamp(-1.0, 1.0);
+    }
+
+    pub fn set_voice_pan(&mut self, id: VoiceId, pan: f32) {
+        let width = self.stereo_width;
+        if let Some(index) = self.voices.find(id) {
+            self.voices.voices_mut()[index].set_pan(pan, width);
+        }
+    }
+
+    // How far unison oscillators spread across the stereo field, from 0 (mono) to 1 (full width)
+    pub fn set_stereo_width(&mut self, width: f32) {
+        self.stereo_width = width.clamp(0.0, 1.0);
+
+        for voice in self.voices.voices_mut() {
+            let pan = voice.pan;
+            voice.set_pan(pan, self.stereo_width);
+        }
+    }
+
+    // Starts a voice for a MIDI note. Retriggering a held note releases the previous voice,
+    // and when every voice is busy one is stolen according to the pool's steal policy.
+    pub fn note_on(&mut self, note: u8, velocity: u8) -> VoiceId {
+        if self.voices.steal_policy != StealPolicy::SameNote {
+            self.note_off(note);
+            // A note struck again under the sustain pedal replaces its ringing voice
+            for voice in self.voices.voices_mut() {
+                if voice.sustained && voice.note == note {
+                    voice.release();
+                }
+            }
+        }
+
+        let pending = PendingNote {
+            id: self.voices.next_id(),
+            note,
+            velocity,
+        };
+
+        match self.voices.allocate(note) {
+            Allocation::Free(index) => self.start_voice(index, pending),
+            Allocation::Steal(index) => {
+                let fade_length = (STEAL_FADE_MS / 1000.0 * self.sample_rate) as u32;
+                self.voices.voices_mut()[index].steal(pending, fade_length);
             }
         }
+        pending.id
     }
 
-    pub fn get_sample_clock(&self) -> f32 {
-        self.sample_clock.load(Ordering::Relaxed) as f32 / self.sample_rate
+    fn start_voice(&mut self, index: usize, pending: PendingNote) {
+        let base_freq = self.get_frequency(pending.note);
+        let num_oscillators = (self.num_oscillators as usize).min(MAX_OSCILLATORS);
+        let frequencies: [f32; MAX_OSCILLATORS] = std::array::from_fn(|i| self.get_detuned_frequency(base_freq, i as u32));
+        let (waveform, quality) = (self.current_waveform, self.quality);
+        let envelope = Envelope::new(self.adsr, self.sample_rate);
+        let filter_envelope = Envelope::new(self.patch.filter.envelope, self.sample_rate);
+        // Only used by LFOs retriggered per voice; global ones read `lfo_clocks`
+        let lfos: [LfoState; LFO_COUNT] = std::array::from_fn(|lfo| {
+            LfoState::new((pending.id.0 as u32 ^ lfo as u32).wrapping_mul(0x9E37_79B9))
+        });
+        let clock = self.sample_clock;
+
+        let voice = &mut self.voices.voices_mut()[index];
+        voice.start(pending, envelope, filter_envelope, lfos, clock);
+        voice.oscillators.reset(&frequencies[..num_oscillators], waveform, quality);
+        voice.set_pan(self.pan, self.stereo_width);
     }
-    
-    pub fn remove_note(&mut self, key: Keycode) {
-        if self.active_keys.remove(&key) {
-            if let Some(envelope) = self.key_envelopes.get_mut(&key) {
-                envelope.trigger_release();
+
+    pub fn note_off(&mut self, note: u8) {
+        while let Some(index) = self.voices.find_held(note) {
+            let voice = &mut self.voices.voices_mut()[index];
+            if self.sustain {
+                voice.sustain();
+            } else {
+                voice.release();
+            }
+        }
+
+        // The note may still be waiting for a stolen voice to fade out
+        for voice in self.voices.voices_mut() {
+            if voice.pending().is_some_and(|pending| pending.note == note) {
+                voice.cancel_pending();
+            }
+        }
+    }
+
+    pub fn release_voice(&mut self, id: VoiceId) {
+        if let Some(index) = self.voices.find(id) {
+            self.voices.voices_mut()[index].release();
+        }
+
+        for voice in self.voices.voices_mut() {
+            if voice.pending().is_some_and(|pending| pending.id == id) {
+                voice.cancel_pending();
+            }
+        }
+    }
+
+    // Releases every note, held or sustained, letting them ring out
+    pub fn release_all(&mut self) {
+        for voice in self.voices.voices_mut() {
+            if voice.held || voice.sustained {
+                voice.release();
             }
+            voice.cancel_pending();
         }
     }
 
+    pub fn clear_voices(&mut self) {
+        for voice in self.voices.voices_mut() {
+            voice.kill();
+        }
+    }
+
+    pub fn active_voice_count(&self) -> usize {
+        self.voices.active_count()
+    }
+
+    pub fn set_max_polyphony(&mut self, max_polyphony: usize) {
+        self.voices.set_max_polyphony(max_polyphony);
+    }
+
+    pub fn set_steal_policy(&mut self, policy: StealPolicy) {
+        self.voices.steal_policy = policy;
+    }
+
+    // Number of samples rendered so far
+    pub fn get_sample_clock(&self) -> u64 {
+        self.sample_clock
+    }
+    
     pub fn toggle_waveform(&mut self) {
-        self.current_waveform.toggle();
+        let mut waveform = self.current_waveform;
+        waveform.toggle();
+        self.set_waveform(waveform);
+    }
+
+    pub fn set_waveform(&mut self, waveform: WaveForm) {
+        self.current_waveform = waveform;
         
-        for oscillators in self.oscillators.values_mut() {
-            for osc in oscillators.iter_mut() {
-                osc.set_waveform(self.current_waveform.clone());
-            }
+        for voice in self.voices.voices_mut() {
+            voice.oscillators.waveform = waveform;
         }
     }
-}
 
-lazy_static! {
-    static ref FREQUENCY_MAP: HashMap<PitchClass, f32> = {
-        let mut m = HashMap::new();
-        let base_frequency = 440.0; // A4
-        let a4 = PitchClass::new(Note::A, 4);
-
-        for octave in 0..10 {
-            for note in [Note::C, Note::CSharp, Note::D, Note::DSharp, Note::E, Note::F, 
-                        Note::FSharp, Note::G, Note::GSharp, Note::A, Note::ASharp, Note::B].iter() {
-                let pitch_class = PitchClass::new(*note, octave);
-                let semitone_difference = (pitch_class.octave - a4.octave) * 12 +
-                    (*note as i32 - Note::A as i32);
-                let frequency = base_frequency * 2f32.powf(semitone_difference as f32 / 12.0);
-                m.insert(pitch_class, frequency);
+    pub fn set_quality(&mut self, quality: Quality) {
+        self.quality = quality;
+
+        for voice in self.voices.voices_mut() {
+            voice.oscillators.quality = quality;
+        }
+    }
+
+    pub fn toggle_quality(&mut self) {
+        let mut quality = self.quality;
+        quality.toggle();
+        self.set_quality(quality);
+    }
+
+    pub fn apply(&mut self, command: Command) {
+        let _realtime = RealtimeScope::enter();
+        match command {
+            Command::NoteOn { note, velocity } => {
+                self.note_on(note, velocity);
             }
+            Command::NoteOff { note } => self.note_off(note),
+            Command::AllNotesOff => self.release_all(),
+            Command::Panic => self.clear_voices(),
+            Command::SetDetune(detune) => self.set_detune(detune),
+            Command::SetMasterVolume(volume) => self.set_master_volume(volume),
+            Command::SetWaveform(waveform) => self.set_waveform(waveform),
+            Command::ToggleWaveform => self.toggle_waveform(),
+            Command::SetQuality(quality) => self.set_quality(quality),
+            Command::ToggleQuality => self.toggle_quality(),
+            Command::SetPan(pan) => self.set_pan(pan),
+            Command::SetStereoWidth(width) => self.set_stereo_width(width),
+            Command::SetMaxPolyphony(max_polyphony) => self.set_max_polyphony(max_polyphony),
+            Command::SetStealPolicy(policy) => self.set_steal_policy(policy),
+            Command::PitchBend(bend) => self.set_pitch_bend(bend),
+            Command::SetPitchBendRange(semitones) => self.set_pitch_bend_range(semitones),
+            Command::SetModWheel(amount) => self.set_mod_wheel(amount),
+            Command::SetSustain(sustain) => self.set_sustain(sustain),
+            Command::ControlChange { controller, value } => self.control_change(controller, value),
+            Command::SetFilterEnabled(enabled) => self.set_filter_enabled(enabled),
+            Command::SetFilterMode(mode) => self.set_filter_mode(mode),
+            Command::SetFilterModel(model) => self.set_filter_model(model),
+            Command::SetFilterCutoff(cutoff) => self.set_filter_cutoff(cutoff),
+            Command::SetFilterResonance(resonance) => self.set_filter_resonance(resonance),
+            Command::SetFilterKeyTracking(amount) => self.set_filter_key_tracking(amount),
+            Command::SetFilterEnvelopeAmount(octaves) => self.set_filter_envelope_amount(octaves),
+            Command::SetFilterEnvelope(adsr) => self.set_filter_envelope(adsr),
+            Command::SetLfo { index, settings } => self.set_lfo(index, settings),
+            Command::SetTempo(tempo) => self.set_tempo(tempo),
+            Command::SetModSlot { index, slot } => self.set_mod_slot(index, slot),
+        }
+    }
+
+    pub fn snapshot(&self) -> SynthSnapshot {
+        SynthSnapshot {
+            detune:             self.detune,
+            master_volume:      self.master_volume,
+            pan:                self.pan,
+            stereo_width:       self.stereo_width,
+            waveform:           self.current_waveform,
+            quality:            self.quality,
+            active_voices:      self.active_voice_count(),
+            max_polyphony:      self.voices.max_polyphony(),
+            steal_policy:       self.voices.steal_policy,
+            pitch_bend:         self.pitch_bend,
+            mod_wheel:          self.mod_wheel,
+            sustain:            self.sustain,
+            patch:              self.patch,
+            tempo:              self.tempo,
         }
-        m
-    };
+    }
+}
+
+// Maps a 0 to 1 control exponentially across the audible range, so equal steps
+// sound like equal changes in brightness
+pub fn cutoff_from_control(amount: f32) -> f32 {
+    MIN_CUTOFF * (MAX_CUTOFF / MIN_CUTOFF).powf(amount.clamp(0.0, 1.0))
+}
+
+pub fn control_from_cutoff(cutoff: f32) -> f32 {
+    (cutoff.clamp(MIN_CUTOFF, MAX_CUTOFF) / MIN_CUTOFF).ln() / (MAX_CUTOFF / MIN_CUTOFF).ln()
+}
+
+pub fn soft_clip(x: f32) -> f32 {
+    let threshold = 0.95;
+    if x.abs() > threshold {
+        threshold * (x / x.abs())
+    } else {
+        x
+    }
 }
@@ -1,70 +1,134 @@
-use super::synth::Synth;
+use super::audio_tap::AudioTap;
+use super::synth::{self, Synth, CHANNELS};
+use super::command::{Command, SynthSnapshot};
+use super::controller::{StopSignal, SynthController};
+use super::spsc::{self, Consumer};
+use super::triple_buffer::{triple_buffer, Input};
+use crate::alloc_guard::RealtimeScope;
+#[cfg(feature = "rodio")]
 use rodio::Source;
-use parking_lot::Mutex;
-use std::sync::Arc;
+#[cfg(feature = "rodio")]
 use std::time::Duration;
 
+const COMMAND_QUEUE_SIZE: usize = 1024;
+pub const DEFAULT_BLOCK_SIZE: usize = 256; // Frames per rendered block
+
+// Length of the fade to silence when the source is stopped
+const STOP_FADE_MS: f32 = 20.0;
+
 pub struct SynthSource {
-    synth: Arc<Mutex<Synth>>,
+    synth: Synth,
+    commands: Consumer<Command>,
+    snapshots: Input<SynthSnapshot>,
     sample_rate: u32,
     buffer: Vec<f32>,
     buffer_pos: usize,
+    stop: StopSignal,
+    fade_remaining: Option<u32>,
+    fade_length: u32,
+    finished: bool,
+    tap: Option<AudioTap>,
 }
 
 impl SynthSource {
-    pub fn new(synth: Arc<Mutex<Synth>>, sample_rate: u32) -> Self {
-        SynthSource {
+    // The source owns the synth; everything else talks to it through the returned controller
+    pub fn new(synth: Synth, sample_rate: u32) -> (Self, SynthController) {
+        Self::with_block_size(synth, sample_rate, DEFAULT_BLOCK_SIZE)
+    }
+
+    pub fn with_block_size(synth: Synth, sample_rate: u32, block_size: usize) -> (Self, SynthController) {
+        let block_size = block_size.max(1);
+        let (command_tx, command_rx) = spsc::channel(COMMAND_QUEUE_SIZE);
+        let (snapshot_tx, snapshot_rx) = triple_buffer(synth.snapshot());
+        let stop = StopSignal::new();
+        let controller = SynthController::new(command_tx, snapshot_rx, stop.clone());
+        let fade_length = ((STOP_FADE_MS / 1000.0 * sample_rate as f32) as u32).max(1);
+
+        let source = SynthSource {
             synth,
+            commands: command_rx,
+            snapshots: snapshot_tx,
             sample_rate,
-            buffer: Vec::with_capacity(256), // Small buffer size
-            buffer_pos: 0,
-        }
+            buffer: vec![0.0; block_size * CHANNELS],
+            buffer_pos: block_size * CHANNELS,
+            stop,
+            fade_remaining: None,
+            fade_length,
+            finished: false,
+            tap: None,
+        };
+        (source, controller)
+    }
+
+    pub fn synth(&self) -> &Synth {
+        &self.synth
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.buffer.len() / CHANNELS
+    }
+
+    // True once a stop has faded all the way out; from then on only silence is rendered
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Copies everything rendered from here on, exactly as it is played, into `tap`
+    pub fn set_tap(&mut self, tap: AudioTap) {
+        self.tap = Some(tap);
     }
 
     pub fn soft_clip(x: f32) -> f32 {
-        let threshold = 0.95;
-        if x.abs() > threshold {
-            threshold * (x / x.abs())
-        } else {
-            x
-        }
+        synth::soft_clip(x)
     }
 
-    fn fill_buffer(&mut self) {
-        self.buffer.clear();
-        let mut synth = self.synth.lock();
-
-        let scaling_factor = synth.get_polyphonic_scaling_factor();
-        
-        // Pre-filter active keys for faster processing
-        let active_keys: Vec<_> = synth.key_envelopes.iter_mut()
-            .filter_map(|(key, envelope)| {
-                envelope.update();
-                if !envelope.is_finished() {
-                    Some(*key)
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        // Generate samples in bulk for better performance
-        for _ in 0..256 {
-            let sample = active_keys.iter()
-                .map(|&key| synth.generate_waveform(key))
-                .sum::<f32>();
-
-            self.buffer.push(Self::soft_clip(sample * scaling_factor));
-            synth.increment_sample_clock();
+    // Applies queued commands, then renders a block. Hosts that pull audio in their
+    // own buffers call this directly instead of going through the iterator.
+    pub fn process(&mut self, out: &mut [f32]) {
+        let _realtime = RealtimeScope::enter();
+        if self.finished {
+            out.fill(0.0);
+            return;
         }
 
-        // Cleanup finished envelopes and keys
-        // In SynthSource::fill_buffer()
-        synth.key_envelopes.retain(|_, envelope| !envelope.is_finished());
-        let keys_to_retain: Vec<_> = synth.key_envelopes.keys().cloned().collect();
-        synth.active_keys.retain(|key| keys_to_retain.contains(key));
-        synth.oscillators.retain(|key, _| keys_to_retain.contains(key));
-        
+        while let Some(command) = self.commands.pop() {
+            self.synth.apply(command);
+        }
+
+        self.synth.process(out);
+
+        if self.fade_remaining.is_none() && self.stop.is_requested() {
+            self.fade_remaining = Some(self.fade_length);
+        }
+        if let Some(remaining) = self.fade_remaining.as_mut() {
+            for frame in out.chunks_exact_mut(CHANNELS) {
+                let gain = *remaining as f32 / self.fade_length as f32;
+                frame.iter_mut().for_each(|sample| *sample *= gain);
+                *remaining = remaining.saturating_sub(1);
+            }
+            if *remaining == 0 {
+                self.synth.clear_voices();
+                self.finished = true;
+            }
+        }
+
+        if let Some(tap) = self.tap.as_mut() {
+            tap.write(out);
+        }
+
+        // The UI only ever needs the latest state
+        self.snapshots.write(self.synth.snapshot());
+    }
+
+    fn fill_buffer(&mut self) {
+        // Taking the Vec out and putting it back never allocates
+        let mut buffer = std::mem::take(&mut self.buffer);
+        self.process(&mut buffer);
+        self.buffer = buffer;
         self.buffer_pos = 0;
     }
 }
@@ -72,8 +136,12 @@ impl SynthSource {
 impl Iterator for SynthSource {
     type Item = f32;
 
+    // Ends at the first block boundary after a stop has faded out
     fn next(&mut self) -> Option<Self::Item> {
         if self.buffer_pos >= self.buffer.len() {
+            if self.finished {
+                return None;
+            }
             self.fill_buffer();
         }
 
@@ -83,13 +151,14 @@ impl Iterator for SynthSource {
     }
 }
 
+#[cfg(feature = "rodio")]
 impl Source for SynthSource {
     fn current_frame_len(&self) -> Option<usize> {
-        Some(256) // Match the minimal buffer size
+        Some(self.buffer.len() - self.buffer_pos) // Samples left in the current block
     }
 
     fn channels(&self) -> u16 {
-        1
+        CHANNELS as u16
     }
 
     fn sample_rate(&self) -> u32 {
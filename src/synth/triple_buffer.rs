@@ -0,0 +1,66 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+const INDEX_MASK: u8 = 0b011;
+const NEW_DATA: u8 = 0b100;
+
+// Lock-free "latest value" channel: the writer always has a free slot to publish
+// into and the reader always sees the most recent complete value, so neither
+// side ever waits for the other and stale values never pile up.
+struct Shared<T> {
+    slots:  [UnsafeCell<T>; 3],
+    back:   AtomicU8, // index of the spare slot, plus NEW_DATA when it holds an unread value
+}
+
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+pub struct Input<T> {
+    shared:     Arc<Shared<T>>,
+    index:      u8,
+}
+
+pub struct Output<T> {
+    shared:     Arc<Shared<T>>,
+    index:      u8,
+}
+
+pub fn triple_buffer<T: Clone>(initial: T) -> (Input<T>, Output<T>) {
+    let shared = Arc::new(Shared {
+        slots: [
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial),
+        ],
+        back: AtomicU8::new(2),
+    });
+
+    (
+        Input { shared: Arc::clone(&shared), index: 0 },
+        Output { shared, index: 1 },
+    )
+}
+
+impl<T> Input<T> {
+    pub fn write(&mut self, value: T) {
+        // Only the writer touches the slot at `self.index`
+        unsafe { *self.shared.slots[self.index as usize].get() = value };
+        let previous = self.shared.back.swap(self.index | NEW_DATA, Ordering::AcqRel);
+        self.index = previous & INDEX_MASK;
+    }
+}
+
+impl<T> Output<T> {
+    pub fn has_update(&self) -> bool {
+        self.shared.back.load(Ordering::Relaxed) & NEW_DATA != 0
+    }
+
+    pub fn read(&mut self) -> &T {
+        if self.has_update() {
+            let previous = self.shared.back.swap(self.index, Ordering::AcqRel);
+            self.index = previous & INDEX_MASK;
+        }
+        // Only the reader touches the slot at `self.index`
+        unsafe { &*self.shared.slots[self.index as usize].get() }
+    }
+}
@@ -0,0 +1,391 @@
+use super::adsr::ADSR;
+use super::envelope::{Envelope, EnvelopeStage};
+use super::filter::Filter;
+use super::lfo::{LfoRetrigger, LfoState, LFO_COUNT};
+use super::oscillator_bank::{OscillatorBank, LANES};
+use super::patch::{ModDestination, ModSources, Patch};
+use super::waveform::{white_noise, PULSE_WIDTH};
+
+// Upper bound on oscillators per voice: one SIMD vector's worth
+pub const MAX_OSCILLATORS: usize = LANES;
+
+// Samples between control-rate updates: LFOs, the filter cutoff and other modulation
+pub const CONTROL_INTERVAL: u32 = 32;
+
+// Synth-wide settings voices read while rendering
+#[derive(Debug, Clone, Copy)]
+pub struct VoiceSettings<'a> {
+    pub patch:          &'a Patch,
+    pub tempo:          f32,
+    pub stereo_width:   f32,
+    pub detune:         f32,
+    pub mod_wheel:      f32,
+    // The synth's global LFO clocks as of the start of the block, and the frame
+    // within the block this render starts at
+    pub lfo_clocks:     &'a [LfoState; LFO_COUNT],
+    pub frame:          usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoiceId(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceState {
+    Free,
+    Playing,
+    // Fading out after being stolen; `pending` starts once the fade completes
+    Stealing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PendingNote {
+    pub id:         VoiceId,
+    pub note:       u8,
+    pub velocity:   u8,
+}
+
+pub struct Voice {
+    pub id:                 VoiceId,
+    pub state:              VoiceState,
+    pub note:               u8,
+    pub velocity:           f32,
+    pub pan:                f32,
+    pub held:               bool,
+    // Key released while the sustain pedal was down; released when the pedal comes up
+    pub sustained:          bool,
+    pub started_at:         u64,
+    pub envelope:           Envelope,
+    pub oscillators:        OscillatorBank,
+    pub filter:             Filter,
+    pub filter_envelope:    Envelope,
+    // Whether the filter ran on the last frame
+    filtering:              bool,
+    // Cycles for LFOs retriggered per voice; global ones follow the synth's clocks
+    pub lfos:               [LfoState; LFO_COUNT],
+    // The mod matrix's random source, drawn when the note starts
+    pub random:             f32,
+    // Envelope times as the note started, before modulation
+    adsr:                   ADSR,
+    // Modulation worked out at the last control-rate update
+    gain:                   f32,
+    gain_step:              f32,
+    pan_offset:             f32,
+    detune_offset:          f32,
+    control_countdown:      u32,
+    fade_remaining:         u32,
+    fade_length:            u32,
+    pending:                Option<PendingNote>,
+}
+
+impl Voice {
+    pub fn new(envelope: Envelope, oscillators: OscillatorBank) -> Self {
+        Voice {
+            id: VoiceId(0),
+            state: VoiceState::Free,
+            note: 0,
+            velocity: 0.0,
+            pan: 0.0,
+            held: false,
+            sustained: false,
+            started_at: 0,
+            envelope,
+            oscillators,
+            filter: Filter::new(),
+            filter_envelope: envelope,
+            filtering: false,
+            lfos: [LfoState::new(1); LFO_COUNT],
+            random: 0.0,
+            adsr: envelope.adsr,
+            gain: 1.0,
+            gain_step: 0.0,
+            pan_offset: 0.0,
+            detune_offset: 0.0,
+            control_countdown: 0,
+            fade_remaining: 0,
+            fade_length: 0,
+            pending: None,
+        }
+    }
+
+    // Resets the voice for a new note; the caller resets the oscillator bank
+    pub fn start(
+        &mut self,
+        pending: PendingNote,
+        envelope: Envelope,
+        filter_envelope: Envelope,
+        lfos: [LfoState; LFO_COUNT],
+        started_at: u64,
+    ) {
+        self.id = pending.id;
+        self.state = VoiceState::Playing;
+        self.note = pending.note;
+        self.velocity = pending.velocity.min(127) as f32 / 127.0;
+        self.held = true;
+        self.sustained = false;
+        self.started_at = started_at;
+        self.envelope = envelope;
+        self.envelope.trigger_attack();
+        self.filter_envelope = filter_envelope;
+        self.filter_envelope.trigger_attack();
+        self.filter.reset();
+        self.filtering = false;
+        self.lfos = lfos;
+        let mut seed = (pending.id.0 as u32).wrapping_mul(0x9E37_79B9) | 1;
+        self.random = white_noise(&mut seed);
+        self.adsr = envelope.adsr;
+        self.gain = 1.0;
+        self.gain_step = 0.0;
+        self.pan_offset = 0.0;
+        self.detune_offset = 0.0;
+        self.control_countdown = 0;
+        self.pending = None;
+    }
+
+    // Places the voice at `pan` and fans its unison oscillators out by `width` around it
+    pub fn set_pan(&mut self, pan: f32, width: f32) {
+        self.pan = pan.clamp(-1.0, 1.0);
+        self.update_pans(width, 0);
+    }
+
+    // Moves the oscillators' pans over `frames` frames, or at once for 0
+    fn update_pans(&mut self, width: f32, frames: u32) {
+        let count = self.oscillators.count;
+        let center = self.pan + self.pan_offset;
+
+        let pans: [f32; MAX_OSCILLATORS] = std::array::from_fn(|index| {
+            let spread = if count > 1 {
+                index as f32 / (count - 1) as f32 * 2.0 - 1.0
+            } else {
+                0.0
+            };
+            center + spread * width
+        });
+        self.oscillators.ramp_pans(&pans[..count], frames);
+    }
+
+    pub fn release(&mut self) {
+        self.held = false;
+        self.sustained = false;
+        self.envelope.trigger_release();
+        self.filter_envelope.trigger_release();
+    }
+
+    // Lets go of the key but keeps the note sounding until `release`
+    pub fn sustain(&mut self) {
+        self.held = false;
+        self.sustained = true;
+    }
+
+    // Fades the current note out over `fade_length` samples, then hands the slot to `pending`
+    pub fn steal(&mut self, pending: PendingNote, fade_length: u32) {
+        if self.state != VoiceState::Stealing {
+            self.state = VoiceState::Stealing;
+            self.held = false;
+            self.sustained = false;
+            self.fade_length = fade_length.max(1);
+            self.fade_remaining = self.fade_length;
+        }
+        self.pending = Some(pending);
+    }
+
+    pub fn pending(&self) -> Option<&PendingNote> {
+        self.pending.as_ref()
+    }
+
+    pub fn cancel_pending(&mut self) {
+        self.pending = None;
+    }
+
+    // Only yields the pending note once the stolen voice has faded out
+    pub fn take_pending(&mut self) -> Option<PendingNote> {
+        if self.state == VoiceState::Free {
+            self.pending.take()
+        } else {
+            None
+        }
+    }
+
+    pub fn kill(&mut self) {
+        self.state = VoiceState::Free;
+        self.held = false;
+        self.sustained = false;
+        self.pending = None;
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.state == VoiceState::Free
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.envelope.is_finished()
+    }
+
+    // Current level, used to find the quietest voice to steal
+    pub fn loudness(&self) -> f32 {
+        match self.state {
+            VoiceState::Free => 0.0,
+            VoiceState::Playing => self.envelope.amplitude * self.velocity,
+            VoiceState::Stealing => {
+                self.envelope.amplitude * self.velocity * self.fade_remaining as f32 / self.fade_length as f32
+            }
+        }
+    }
+
+    // Adds the voice into the interleaved stereo `out` until the block ends or the
+    // voice frees up, returning the number of frames rendered
+    pub fn render(&mut self, out: &mut [f32], settings: &VoiceSettings) -> usize {
+        for (index, frame) in out.chunks_exact_mut(2).enumerate() {
+            if self.is_free() {
+                return index;
+            }
+            let (left, right) = self.next_frame(settings, settings.frame + index);
+            frame[0] += left;
+            frame[1] += right;
+        }
+        out.len() / 2
+    }
+
+    // Renders one stereo frame, `frame` frames into the block, advancing the
+    // envelopes and any steal fade
+    pub fn next_frame(&mut self, settings: &VoiceSettings, frame: usize) -> (f32, f32) {
+        if self.control_countdown == 0 {
+            self.update_controls(settings, frame);
+            self.control_countdown = CONTROL_INTERVAL;
+        }
+        self.control_countdown -= 1;
+
+        let gain = match self.state {
+            VoiceState::Free => return (0.0, 0.0),
+            VoiceState::Playing => {
+                let amplitude = self.envelope.next_sample();
+                if self.envelope.is_finished() {
+                    self.state = VoiceState::Free;
+                }
+                amplitude * self.velocity
+            }
+            VoiceState::Stealing => {
+                let amplitude = self.envelope.next_sample();
+                let fade = self.fade_remaining as f32 / self.fade_length as f32;
+                self.fade_remaining = self.fade_remaining.saturating_sub(1);
+                if self.fade_remaining == 0 || self.envelope.is_finished() {
+                    self.state = VoiceState::Free;
+                }
+                amplitude * self.velocity * fade
+            }
+        };
+
+        self.filter_envelope.next_sample();
+        let (left, right) = self.oscillators.next_frame();
+        let (left, right) = if settings.patch.filter.enabled {
+            // Switched on mid-note, it starts from rest rather than from state left over
+            // from the last time it ran
+            if !self.filtering {
+                self.filter.reset();
+                self.filtering = true;
+            }
+            (self.filter.process(0, left), self.filter.process(1, right))
+        } else {
+            self.filtering = false;
+            (left, right)
+        };
+        let gain = gain * self.gain;
+        self.gain += self.gain_step;
+        (left * gain, right * gain)
+    }
+
+    // Steps the LFOs on by one control interval, then evaluates the patch's
+    // modulation for this voice and applies it
+    fn update_controls(&mut self, settings: &VoiceSettings, frame: usize) {
+        let patch = settings.patch;
+        let sample_rate = self.envelope.sample_rate;
+
+        let mut lfos = [0.0; LFO_COUNT];
+        for (index, (lfo, value)) in patch.lfos.iter().zip(&mut lfos).enumerate() {
+            let step = lfo.frequency(settings.tempo) / sample_rate;
+            let state = &mut self.lfos[index];
+            *value = match lfo.retrigger {
+                // Read from the shared clock rather than a copy, so every voice stays on
+                // the same cycle through rate and tempo changes
+                LfoRetrigger::Global => {
+                    let mut clock = settings.lfo_clocks[index];
+                    clock.advance(step * frame as f32);
+                    clock.value(lfo)
+                }
+                LfoRetrigger::PerVoice => state.value(lfo),
+            };
+            state.advance(step * CONTROL_INTERVAL as f32);
+        }
+        let sources = ModSources {
+            lfos,
+            amp_envelope: self.envelope.amplitude,
+            filter_envelope: self.filter_envelope.amplitude,
+            velocity: self.velocity,
+            key: (self.note as f32 - 63.5) / 63.5,
+            mod_wheel: settings.mod_wheel,
+            random: self.random,
+        };
+        let targets = patch.evaluate(&sources);
+
+        // Level and pan ramp across the interval to the new values, since a step every
+        // 32 samples is audible as zipper noise. A note's first update has nothing to
+        // ramp from.
+        let starting = self.envelope.stage == EnvelopeStage::Attack && self.envelope.elapsed_samples == 0;
+        let ramp = if starting { 0 } else { CONTROL_INTERVAL };
+
+        let gain = (1.0 + targets.get(ModDestination::Level)).max(0.0);
+        if starting {
+            self.gain = gain;
+            self.gain_step = 0.0;
+        } else {
+            self.gain_step = (gain - self.gain) / CONTROL_INTERVAL as f32;
+        }
+        self.oscillators.set_pitch_ratio((targets.get(ModDestination::Pitch) / 12.0).exp2());
+        self.oscillators.set_pulse_width(PULSE_WIDTH + targets.get(ModDestination::PulseWidth));
+
+        let pan = targets.get(ModDestination::Pan);
+        if pan != self.pan_offset {
+            self.pan_offset = pan;
+            self.update_pans(settings.stereo_width, ramp);
+        }
+
+        // Also follows changes to the synth's own detune while an offset is applied
+        let detune = targets.get(ModDestination::Detune);
+        if detune != 0.0 || self.detune_offset != 0.0 {
+            self.detune_offset = detune;
+            self.update_spread(settings.detune);
+        }
+
+        // Envelope times scale by octaves, so +1 doubles them
+        let scale = |octaves: f32, time: std::time::Duration| time.mul_f32(octaves.clamp(-8.0, 8.0).exp2());
+        self.envelope.adsr = ADSR {
+            attack: scale(targets.get(ModDestination::Attack), self.adsr.attack),
+            decay: scale(targets.get(ModDestination::Decay), self.adsr.decay),
+            release: scale(targets.get(ModDestination::Release), self.adsr.release),
+            ..self.adsr
+        };
+
+        // Kept up to date while the filter is off too, so switching it on mid-interval
+        // never runs through stale coefficients
+        let filter = &patch.filter;
+        let cutoff = filter.cutoff_for(self.note, self.filter_envelope.amplitude)
+            * targets.get(ModDestination::Cutoff).exp2();
+        let resonance = filter.resonance + targets.get(ModDestination::Resonance);
+        self.filter.set(filter.mode, filter.model, cutoff, resonance, sample_rate);
+    }
+
+    // Moves each unison oscillator to where the synth's detune plus this voice's
+    // offset would put it; see `Synth::get_detuned_frequency`
+    fn update_spread(&mut self, detune: f32) {
+        let count = self.oscillators.count;
+        if count < 2 {
+            return;
+        }
+
+        let modulated = (detune + self.detune_offset).max(0.0);
+        let ratios: [f32; LANES] = std::array::from_fn(|lane| {
+            let position = (lane.min(count - 1) as f32 / (count - 1) as f32) - 0.5;
+            (1.0 + modulated / 100.0 * position) / (1.0 + detune / 100.0 * position)
+        });
+        self.oscillators.set_lane_ratios(&ratios);
+    }
+}
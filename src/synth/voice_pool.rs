@@ -0,0 +1,118 @@
+use super::envelope::Envelope;
+use super::oscillator_bank::OscillatorBank;
+use super::voice::{Voice, VoiceId, VoiceState};
+
+// Size of the preallocated pool; `max_polyphony` can be changed at runtime up to this
+pub const MAX_VOICES: usize = 64;
+pub const DEFAULT_POLYPHONY: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StealPolicy {
+    // Steal the voice that started first
+    Oldest,
+    // Steal the voice with the lowest current level
+    Quietest,
+    // Replaying a sounding note reuses its voice, otherwise steal the oldest
+    SameNote,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Allocation {
+    Free(usize),
+    Steal(usize),
+}
+
+pub struct VoicePool {
+    voices:             Vec<Voice>,
+    max_polyphony:      usize,
+    pub steal_policy:   StealPolicy,
+    next_voice_id:      u64,
+}
+
+impl VoicePool {
+    pub fn new(envelope: Envelope, oscillators: OscillatorBank, max_polyphony: usize) -> Self {
+        VoicePool {
+            voices: (0..MAX_VOICES).map(|_| Voice::new(envelope, oscillators)).collect(),
+            max_polyphony: max_polyphony.clamp(1, MAX_VOICES),
+            steal_policy: StealPolicy::Oldest,
+            next_voice_id: 0,
+        }
+    }
+
+    pub fn voices(&self) -> &[Voice] {
+        &self.voices
+    }
+
+    pub fn voices_mut(&mut self) -> &mut [Voice] {
+        &mut self.voices
+    }
+
+    pub fn max_polyphony(&self) -> usize {
+        self.max_polyphony
+    }
+
+    pub fn set_max_polyphony(&mut self, max_polyphony: usize) {
+        self.max_polyphony = max_polyphony.clamp(1, MAX_VOICES);
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.voices.iter().filter(|voice| !voice.is_free()).count()
+    }
+
+    pub fn held_count(&self) -> usize {
+        self.voices.iter().filter(|voice| voice.held).count()
+    }
+
+    pub fn next_id(&mut self) -> VoiceId {
+        let id = VoiceId(self.next_voice_id);
+        self.next_voice_id += 1;
+        id
+    }
+
+    pub fn find(&self, id: VoiceId) -> Option<usize> {
+        self.voices.iter().position(|voice| !voice.is_free() && voice.id == id)
+    }
+
+    pub fn find_held(&self, note: u8) -> Option<usize> {
+        self.voices.iter().position(|voice| voice.held && voice.note == note)
+    }
+
+    // Picks the slot for a new note, stealing one when the polyphony limit is reached
+    pub fn allocate(&self, note: u8) -> Allocation {
+        if self.steal_policy == StealPolicy::SameNote {
+            if let Some(index) = self.voices.iter().position(|voice| voice.state == VoiceState::Playing && voice.note == note) {
+                return Allocation::Steal(index);
+            }
+        }
+
+        if self.active_count() < self.max_polyphony {
+            if let Some(index) = self.voices.iter().position(Voice::is_free) {
+                return Allocation::Free(index);
+            }
+        }
+
+        let playing = self.voices.iter()
+            .enumerate()
+            .filter(|(_, voice)| voice.state == VoiceState::Playing);
+
+        let victim = match self.steal_policy {
+            StealPolicy::Oldest | StealPolicy::SameNote => {
+                playing.min_by_key(|(_, voice)| voice.started_at).map(|(index, _)| index)
+            }
+            StealPolicy::Quietest => {
+                playing.min_by(|(_, a), (_, b)| a.loudness().total_cmp(&b.loudness())).map(|(index, _)| index)
+            }
+        };
+
+        // Every voice is already fading out, so replace the newest pending note
+        let victim = victim.or_else(|| {
+            self.voices.iter()
+                .enumerate()
+                .filter(|(_, voice)| voice.state == VoiceState::Stealing)
+                .max_by_key(|(_, voice)| voice.pending().map(|pending| pending.id.0))
+                .map(|(index, _)| index)
+        });
+
+        Allocation::Steal(victim.unwrap_or(0))
+    }
+}
@@ -1,7 +1,27 @@
 use std::f32::consts::PI;
-use rand::Rng;
 
-#[derive(Clone)]
+// Naive output aliases at high pitches but is cheap and has a gritty lo-fi
+// character; band-limited output smooths each discontinuity with PolyBLEP
+// (steps) or PolyBLAMP (corners).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    Naive,
+    BandLimited,
+}
+
+impl Quality {
+    pub fn toggle(&mut self) {
+        *self = match self {
+            Quality::Naive => Quality::BandLimited,
+            Quality::BandLimited => Quality::Naive,
+        }
+    }
+}
+
+// Pulse width when nothing modulates it
+pub const PULSE_WIDTH: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WaveForm {
     Sine,
     Saw,
@@ -12,8 +32,18 @@ pub enum WaveForm {
 }
 
 impl WaveForm {
-    pub fn generate(&self, frequency: f32, t: f32) -> f32 {
-        let phase = t * frequency % 1.0;
+    // In toggle order; MIDI program changes select waveforms by index into this list
+    pub const ALL: [WaveForm; 6] = [
+        WaveForm::Sine, WaveForm::Saw, WaveForm::Square,
+        WaveForm::Pulse, WaveForm::Triangle, WaveForm::WhiteNoise,
+    ];
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    // `phase` is the position within one cycle, in [0, 1)
+    pub fn generate(&self, phase: f32) -> f32 {
         match self {
             WaveForm::Sine => (phase * 2.0 * PI).sin(),
             WaveForm::Saw => {
@@ -27,7 +57,7 @@ impl WaveForm {
                 }
             },
             WaveForm::Pulse => {
-                if phase < 0.25 {
+                if phase < PULSE_WIDTH {
                     1.0
                 } else {
                     -1.0
@@ -42,12 +72,22 @@ impl WaveForm {
                     4.0 * phase - 4.0
                 }
             }
-            WaveForm::WhiteNoise => {
-                let mut rng = rand::thread_rng();
-                rng.gen_range(-1.0..1.0)
-            },
+            // Noise has no shape to look up; callers keep their own `white_noise` state
+            WaveForm::WhiteNoise => 0.0,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sine" => Some(WaveForm::Sine),
+            "saw" => Some(WaveForm::Saw),
+            "square" => Some(WaveForm::Square),
+            "pulse" => Some(WaveForm::Pulse),
+            "triangle" => Some(WaveForm::Triangle),
+            "noise" | "whitenoise" => Some(WaveForm::WhiteNoise),
+            _ => None,
         }
-    } 
+    }
 
     pub fn toggle(&mut self) {
         *self = match self {
@@ -59,4 +99,12 @@ impl WaveForm {
             WaveForm::WhiteNoise => WaveForm::Sine,
         }
     }
-}
\ No newline at end of file
+}
+
+// xorshift32 step mapped to [-1, 1); `state` must be non-zero
+pub fn white_noise(state: &mut u32) -> f32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    (*state >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+}
@@ -0,0 +1,79 @@
+use std::io::{stdout, Write};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, Clear, ClearType},
+};
+
+// Whether enhancement flags were pushed and so must be popped again on restore
+static KEYBOARD_ENHANCED: AtomicBool = AtomicBool::new(false);
+
+// Puts the terminal into raw mode with mouse capture for as long as it lives.
+// Dropping it, returning early with an error, or panicking all restore the terminal.
+pub struct TerminalGuard {
+    _private: (),
+}
+
+impl TerminalGuard {
+    pub fn enter() -> std::io::Result<Self> {
+        install_panic_hook();
+        enable_raw_mode()?;
+        // From here on a failure still has to undo raw mode
+        let guard = TerminalGuard { _private: () };
+        execute!(stdout(), EnableMouseCapture, Hide, Clear(ClearType::All))?;
+        Ok(guard)
+    }
+
+    // Asks the terminal to report key repeat and release events. Returns false
+    // if the terminal does not support the keyboard enhancement protocol.
+    pub fn enable_keyboard_enhancement(&self) -> std::io::Result<bool> {
+        if !supports_keyboard_enhancement()? {
+            return Ok(false);
+        }
+        execute!(
+            stdout(),
+            PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+            )
+        )?;
+        KEYBOARD_ENHANCED.store(true, Ordering::SeqCst);
+        Ok(true)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+// Best effort: there is nothing sensible to do if restoring fails
+fn restore() {
+    if KEYBOARD_ENHANCED.swap(false, Ordering::SeqCst) {
+        let _ = execute!(stdout(), PopKeyboardEnhancementFlags);
+    }
+    let _ = execute!(stdout(), DisableMouseCapture, Show);
+    let _ = disable_raw_mode();
+    let _ = stdout().flush();
+}
+
+// The guard is only dropped after the panic message has been printed, which raw
+// mode would garble, so the hook restores the terminal before printing
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore();
+            println!();
+            default_hook(info);
+        }));
+    });
+}
@@ -0,0 +1,75 @@
+use pulsar::render::{render, TimedEvent};
+use pulsar::synth::envelope::Envelope;
+use pulsar::synth::{Command, CHANNELS};
+use pulsar::{Synth, ADSR};
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+#[test]
+fn stages_last_their_length_in_samples() {
+    // 10ms attack, 20ms decay to half, 30ms release
+    let mut envelope = Envelope::new(ADSR::new(10, 20, 0.5, 30), SAMPLE_RATE);
+    envelope.trigger_attack();
+    let held: Vec<f32> = (0..2000).map(|_| envelope.next_sample()).collect();
+
+    assert_eq!(held[0], 0.0);
+    assert_eq!(held[240], 0.5);
+    assert_eq!(held[480], 1.0);
+    assert_eq!(held[480 + 480], 0.75);
+    assert_eq!(held[480 + 960], 0.5);
+    assert_eq!(held[1999], 0.5);
+
+    envelope.trigger_release();
+    let released: Vec<f32> = (0..1440).map(|_| envelope.next_sample()).collect();
+    assert_eq!(released[0], 0.5);
+    assert_eq!(released[720], 0.25);
+    assert!(released.windows(2).all(|pair| pair[1] < pair[0]));
+    assert_eq!(envelope.next_sample(), 0.0);
+    assert!(envelope.is_finished());
+}
+
+// Renders a note held for `held_frames`, `block` frames at a time, until it has rung out
+fn render_in_blocks(held_frames: usize, block: usize) -> Vec<f32> {
+    let mut synth = Synth::new(SAMPLE_RATE, ADSR::new(30, 40, 0.6, 50));
+    let mut output = Vec::new();
+    let mut buffer = vec![0.0; block * CHANNELS];
+
+    synth.note_on(60, 100);
+    while output.len() < held_frames * CHANNELS {
+        let frames = block.min(held_frames - output.len() / CHANNELS);
+        synth.process(&mut buffer[..frames * CHANNELS]);
+        output.extend_from_slice(&buffer[..frames * CHANNELS]);
+    }
+    synth.note_off(60);
+    while synth.active_voice_count() > 0 {
+        synth.process(&mut buffer);
+        output.extend_from_slice(&buffer);
+    }
+    output
+}
+
+#[test]
+fn envelopes_do_not_depend_on_block_size_or_timing() {
+    let held_frames = 4000;
+    let reference = render_in_blocks(held_frames, 256);
+    for block in [1, 37, 64, 1000] {
+        let output = render_in_blocks(held_frames, block);
+        // Each render stops at the first block boundary after the release ends, padding with silence
+        let length = output.len().min(reference.len());
+        assert_eq!(output[..length], reference[..length], "block of {}", block);
+        assert!(output[length..].iter().chain(&reference[length..]).all(|sample| *sample == 0.0));
+    }
+
+    // The offline renderer lands on the same samples, however many times it runs
+    let events = [
+        TimedEvent::new(0.0, Command::NoteOn { note: 60, velocity: 100 }),
+        TimedEvent::new(held_frames as f64 / SAMPLE_RATE as f64, Command::NoteOff { note: 60 }),
+    ];
+    for _ in 0..2 {
+        let mut synth = Synth::new(SAMPLE_RATE, ADSR::new(30, 40, 0.6, 50));
+        let output = render(&mut synth, &events);
+        let length = output.len().min(reference.len());
+        assert_eq!(output[..length], reference[..length]);
+        assert!(output[length..].iter().chain(&reference[length..]).all(|sample| *sample == 0.0));
+    }
+}
@@ -0,0 +1,133 @@
+use pulsar::synth::filter::{Filter, FilterMode, FilterModel, FilterSettings};
+use pulsar::synth::CHANNELS;
+use pulsar::{Synth, ADSR};
+use std::f32::consts::TAU;
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+// Steady-state gain for a quiet sine at `frequency`, so the ladder's saturation stays out of the way
+fn gain(mode: FilterMode, model: FilterModel, cutoff: f32, resonance: f32, frequency: f32) -> f32 {
+    let mut filter = Filter::new();
+    filter.set(mode, model, cutoff, resonance, SAMPLE_RATE);
+
+    let amplitude = 0.05;
+    let length = SAMPLE_RATE as usize;
+    let mut peak: f32 = 0.0;
+    for index in 0..length {
+        let input = amplitude * (TAU * frequency * index as f32 / SAMPLE_RATE).sin();
+        let output = filter.process(0, input);
+        if index > length / 2 {
+            peak = peak.max(output.abs());
+        }
+    }
+    peak / amplitude
+}
+
+#[test]
+fn lowpass_and_highpass_split_the_spectrum() {
+    for model in [FilterModel::StateVariable, FilterModel::Ladder] {
+        assert!(gain(FilterMode::LowPass, model, 1000.0, 0.0, 100.0) > 0.9, "{:?}", model);
+        assert!(gain(FilterMode::LowPass, model, 1000.0, 0.0, 10000.0) < 0.02, "{:?}", model);
+        assert!(gain(FilterMode::HighPass, model, 1000.0, 0.0, 100.0) < 0.02, "{:?}", model);
+        assert!(gain(FilterMode::HighPass, model, 1000.0, 0.0, 10000.0) > 0.9, "{:?}", model);
+    }
+}
+
+#[test]
+fn bandpass_peaks_and_notch_dips_at_the_cutoff() {
+    for model in [FilterModel::StateVariable, FilterModel::Ladder] {
+        let center = gain(FilterMode::BandPass, model, 1000.0, 0.0, 1000.0);
+        assert!((0.9..1.1).contains(&center), "{:?}: {}", model, center);
+        assert!(gain(FilterMode::BandPass, model, 1000.0, 0.0, 50.0) < 0.2, "{:?}", model);
+
+        assert!(gain(FilterMode::Notch, model, 1000.0, 0.0, 1000.0) < 0.05, "{:?}", model);
+        assert!(gain(FilterMode::Notch, model, 1000.0, 0.0, 50.0) > 0.9, "{:?}", model);
+    }
+}
+
+#[test]
+fn resonance_boosts_the_cutoff_and_stays_bounded() {
+    for model in [FilterModel::StateVariable, FilterModel::Ladder] {
+        let flat = gain(FilterMode::LowPass, model, 1000.0, 0.0, 1000.0);
+        let resonant = gain(FilterMode::LowPass, model, 1000.0, 0.9, 1000.0);
+        assert!(resonant > flat * 2.0, "{:?}: {} vs {}", model, resonant, flat);
+    }
+
+    // Full resonance on a loud input must not run away
+    let mut filter = Filter::new();
+    filter.set(FilterMode::LowPass, FilterModel::Ladder, 500.0, 1.0, SAMPLE_RATE);
+    for index in 0..48000 {
+        let input = if index % 200 < 100 { 1.0 } else { -1.0 };
+        let output = filter.process(1, input);
+        assert!(output.is_finite() && output.abs() < 10.0);
+    }
+}
+
+#[test]
+fn cutoff_follows_the_key_and_the_envelope() {
+    let settings = FilterSettings {
+        cutoff: 1000.0,
+        key_tracking: 1.0,
+        envelope_amount: 2.0,
+        ..FilterSettings::default()
+    };
+    assert_eq!(settings.cutoff_for(60, 0.0), 1000.0);
+    assert!((settings.cutoff_for(72, 0.0) - 2000.0).abs() < 0.1);
+    assert!((settings.cutoff_for(60, 0.5) - 2000.0).abs() < 0.1);
+    assert!((settings.cutoff_for(48, 1.0) - 2000.0).abs() < 0.1);
+}
+
+#[test]
+fn closing_the_filter_darkens_a_voice() {
+    fn render(cutoff: Option<f32>) -> f32 {
+        let mut synth = Synth::new(SAMPLE_RATE, ADSR::new(1, 10, 1.0, 10));
+        synth.set_waveform(pulsar::WaveForm::Saw);
+        if let Some(cutoff) = cutoff {
+            synth.set_filter_enabled(true);
+            synth.set_filter_cutoff(cutoff);
+        }
+        synth.note_on(48, 127);
+        let mut block = vec![0.0; 4800 * CHANNELS];
+        synth.process(&mut block);
+        // High-frequency energy: the sum of squared differences between neighbouring frames
+        block.chunks_exact(CHANNELS)
+            .zip(block.chunks_exact(CHANNELS).skip(1))
+            .map(|(a, b)| (b[0] - a[0]).powi(2))
+            .sum()
+    }
+    assert!(render(Some(300.0)) < render(None) * 0.1);
+}
+
+#[test]
+fn enabling_the_filter_mid_note_does_not_drop_out() {
+    // Renders 1000 frames, which ends part way through a control interval, then 64 more
+    // with the filter switched on wide open or left off
+    fn render(enable: bool) -> Vec<f32> {
+        let mut synth = Synth::new(SAMPLE_RATE, ADSR::new(1, 10, 1.0, 10));
+        synth.set_filter_cutoff(20000.0);
+        synth.note_on(57, 60);
+        let mut block = vec![0.0; 1000 * CHANNELS];
+        synth.process(&mut block);
+
+        if enable {
+            synth.set_filter_enabled(true);
+        }
+        let mut block = vec![0.0; 64 * CHANNELS];
+        synth.process(&mut block);
+        block
+    }
+
+    let (open, bypassed) = (render(true), render(false));
+    let peak = bypassed.iter().fold(0.0, |peak: f32, sample| peak.max(sample.abs()));
+    assert!(peak > 0.1);
+
+    // The filter starts from rest, so it takes a few frames to catch up with the
+    // signal, but it never falls silent waiting for its coefficients
+    let quietest = open[..32 * CHANNELS].iter().zip(&bypassed).fold(f32::MAX, |quietest, (a, b)| {
+        if b.abs() > peak * 0.5 { quietest.min(a.abs()) } else { quietest }
+    });
+    assert!(quietest > peak * 0.25, "{} against a peak of {}", quietest, peak);
+
+    let settled = open.iter().zip(&bypassed).skip(8 * CHANNELS).fold(0.0, |worst: f32, (a, b)| worst.max((a - b).abs()));
+    assert!(settled < peak * 0.05, "{} against a peak of {}", settled, peak);
+}
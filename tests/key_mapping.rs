@@ -0,0 +1,105 @@
+use pulsar::input::key_mapping::{KeyMap, KeyMapIssue, Layout};
+use pulsar::synth::note::{Note, PitchClass};
+use pulsar::synth::CHANNELS;
+use pulsar::{StealPolicy, Synth, ADSR};
+
+#[test]
+fn parses_sharps_comments_and_the_hash_key() {
+    let text = "\
+# a comment line
+s = C#2        # trailing comment
+b = Bb2
+# = D#2
+   # indented comment
+";
+    let (map, warnings) = KeyMap::parse(text, Layout::Qwerty).unwrap();
+    assert_eq!(map.get_pitch_class('s'), Some(&PitchClass::new(Note::CSharp, 2)));
+    assert_eq!(map.get_midi_note('b'), Some(46));
+    assert_eq!(map.get_midi_note('#'), Some(39));
+    assert!(warnings.is_empty(), "{:?}", warnings);
+}
+
+#[test]
+fn reports_syntax_errors_with_line_numbers() {
+    let issues = KeyMap::parse("z = C3\nzz = D3\nx = H2\nno equals\nlayout = colemak\n", Layout::Qwerty).unwrap_err();
+    let lines: Vec<usize> = issues.iter()
+        .map(|issue| match issue {
+            KeyMapIssue::Syntax { line, .. } => *line,
+            other => panic!("unexpected {:?}", other),
+        })
+        .collect();
+    assert_eq!(lines, vec![2, 3, 4, 5]);
+}
+
+#[test]
+fn duplicates_warn_and_conflicts_fail() {
+    let (_, warnings) = KeyMap::parse("z = C3\nz = C3\n", Layout::Qwerty).unwrap();
+    assert_eq!(warnings, vec![KeyMapIssue::Duplicate { key: 'z', line: 2, first_line: 1 }]);
+
+    let issues = KeyMap::parse("z = C3\nZ = D3\n", Layout::Qwerty).unwrap_err();
+    assert_eq!(issues, vec![KeyMapIssue::Conflict { key: 'z', line: 2, first_line: 1 }]);
+}
+
+#[test]
+fn shared_notes_are_warnings() {
+    let (map, warnings) = KeyMap::parse("a = C3\n", Layout::Qwerty).unwrap();
+    assert_eq!(map.get_midi_note('a'), map.get_midi_note('z'));
+    assert_eq!(warnings, vec![KeyMapIssue::SharedNote { note: PitchClass::new(Note::C, 3), keys: vec!['a', 'z'] }]);
+    assert!(!warnings[0].is_error());
+
+    assert!(KeyMap::preset(Layout::Dvorak).validate().is_empty());
+}
+
+#[test]
+fn none_unmaps_a_key() {
+    let (map, _) = KeyMap::parse("z = none\n", Layout::Qwerty).unwrap();
+    assert_eq!(map.get_midi_note('z'), None);
+    assert_eq!(map.get_midi_note('x'), Some(50));
+}
+
+#[test]
+fn layout_line_picks_the_preset() {
+    // QWERTZ swaps y and z, so the C3 key prints `y`
+    let (map, _) = KeyMap::parse("layout = qwertz\n", Layout::Qwerty).unwrap();
+    assert_eq!(map.layout(), Layout::Qwertz);
+    assert_eq!(map.get_midi_note('y'), Some(48));
+    assert_eq!(map.get_midi_note('z'), Some(69));
+    assert_eq!(map.get_midi_note_at('z'), Some(48));
+
+    // Assignments apply on top of the named layout wherever the line appears
+    let (map, _) = KeyMap::parse("y = D3\nlayout = azerty\n", Layout::Qwerty).unwrap();
+    assert_eq!(map.layout(), Layout::Azerty);
+    assert_eq!(map.get_midi_note('w'), Some(48));
+    assert_eq!(map.get_midi_note('y'), Some(50));
+}
+
+#[test]
+fn keys_sharing_a_note_drive_one_voice() {
+    let (map, warnings) = KeyMap::parse("z = C4\nm = C4\n", Layout::Qwerty).unwrap();
+    assert!(matches!(warnings.as_slice(), [KeyMapIssue::SharedNote { .. }]));
+    let note = map.get_midi_note('z').unwrap();
+    assert_eq!(map.get_midi_note('m'), Some(note));
+
+    for policy in [StealPolicy::Oldest, StealPolicy::SameNote] {
+        let mut synth = Synth::new(48000.0, ADSR::new(5, 5, 1.0, 20));
+        synth.set_steal_policy(policy);
+        let mut block = vec![0.0; 256 * CHANNELS];
+
+        // Voices are keyed by note, so the second key takes over the first key's note
+        // rather than stacking a second held voice on it
+        for key in ['z', 'm'] {
+            synth.note_on(map.get_midi_note(key).unwrap(), 100);
+            synth.process(&mut block);
+        }
+        let held = synth.voices.voices().iter().filter(|voice| voice.held && voice.note == note).count();
+        assert_eq!(held, 1, "{:?}", policy);
+
+        // So a single note off is enough to let it go
+        synth.note_off(note);
+        assert_eq!(synth.voices.held_count(), 0, "{:?}", policy);
+        for _ in 0..10 {
+            synth.process(&mut block);
+        }
+        assert_eq!(synth.active_voice_count(), 0, "{:?}", policy);
+    }
+}
@@ -0,0 +1,170 @@
+use pulsar::synth::lfo::{LfoDestination, LfoRate, LfoRetrigger, LfoSettings, LfoState};
+use pulsar::synth::CHANNELS;
+use pulsar::{Quality, Synth, WaveForm, ADSR};
+use std::f32::consts::TAU;
+
+fn synth() -> Synth {
+    Synth::new(48000.0, ADSR::new(1, 10, 1.0, 10))
+}
+
+fn lfo(shape: WaveForm, destination: LfoDestination, depth: f32) -> LfoSettings {
+    LfoSettings {
+        shape,
+        rate: LfoRate::Hertz(0.5),
+        destination,
+        depth,
+        ..LfoSettings::default()
+    }
+}
+
+#[test]
+fn synced_rates_follow_the_tempo() {
+    let quarter = LfoSettings { rate: LfoRate::Beats(1.0), ..LfoSettings::default() };
+    let bar = LfoSettings { rate: LfoRate::Beats(4.0), ..LfoSettings::default() };
+    assert_eq!(quarter.frequency(120.0), 2.0);
+    assert_eq!(bar.frequency(120.0), 0.5);
+    assert_eq!(bar.frequency(60.0), 0.25);
+    assert_eq!(LfoSettings::default().frequency(90.0), 5.0);
+}
+
+#[test]
+fn phase_offset_and_sample_and_hold() {
+    let sine = LfoSettings { phase: 0.25, ..LfoSettings::default() };
+    let mut state = LfoState::new(7);
+    assert!((state.value(&sine) - 1.0).abs() < 1e-6);
+    state.advance(0.5);
+    assert!((state.value(&sine) + 1.0).abs() < 1e-6);
+
+    // White noise holds one random level for a whole cycle
+    let noise = LfoSettings { shape: WaveForm::WhiteNoise, ..LfoSettings::default() };
+    let held = state.value(&noise);
+    state.advance(0.25);
+    assert_eq!(state.value(&noise), held);
+    state.advance(0.5);
+    assert_ne!(state.value(&noise), held);
+    assert!((-1.0..1.0).contains(&state.value(&noise)));
+}
+
+#[test]
+fn routes_to_pitch_and_pulse_width() {
+    let mut synth = synth();
+    // A square wave starts high, so the first control update applies the full depth
+    synth.set_lfo(0, lfo(WaveForm::Square, LfoDestination::Pitch, 12.0));
+    synth.set_lfo(1, lfo(WaveForm::Square, LfoDestination::PulseWidth, 0.5));
+    synth.note_on(60, 100);
+    let mut block = vec![0.0; 256 * CHANNELS];
+    synth.process(&mut block);
+
+    let voice = synth.voices.voices().iter().find(|voice| !voice.is_free()).unwrap();
+    assert!((voice.oscillators.pitch_ratio - 2.0).abs() < 1e-6);
+    assert!((voice.oscillators.pulse_width - 0.5).abs() < 1e-6);
+}
+
+#[test]
+fn amplitude_lfo_can_silence_a_voice() {
+    fn peak(lfo: Option<LfoSettings>) -> f32 {
+        let mut synth = synth();
+        if let Some(lfo) = lfo {
+            synth.set_lfo(0, lfo);
+        }
+        synth.note_on(69, 127);
+        let mut block = vec![0.0; 2048 * CHANNELS];
+        synth.process(&mut block);
+        block.iter().fold(0.0, |peak: f32, sample| peak.max(sample.abs()))
+    }
+
+    let trough = LfoSettings { phase: 0.5, ..lfo(WaveForm::Square, LfoDestination::Amplitude, 1.0) };
+    assert!(peak(None) > 0.1);
+    assert!(peak(Some(trough)) < 1e-6);
+}
+
+#[test]
+fn global_lfos_keep_every_voice_in_step() {
+    let mut synth = synth();
+    synth.num_oscillators = 1;
+    let mut settings = lfo(WaveForm::Sine, LfoDestination::Pan, 0.5);
+    settings.rate = LfoRate::Hertz(3.0);
+    settings.retrigger = LfoRetrigger::Global;
+    synth.set_lfo(0, settings);
+
+    // Voices' pans, which the LFO moves
+    fn pans(synth: &Synth) -> Vec<f32> {
+        synth.voices.voices().iter()
+            .filter(|voice| !voice.is_free())
+            .map(|voice| voice.oscillators.pans[0])
+            .collect()
+    }
+
+    // Voices update their modulation every 32 frames, each on their own schedule, so
+    // they can trail the clock by that much
+    fn assert_following_clock(synth: &Synth, context: &str) {
+        let lfo = &synth.patch.lfos[0];
+        let expected = lfo.depth * synth.lfo_clocks[0].value(lfo);
+        let tolerance = lfo.depth * TAU * lfo.frequency(synth.tempo) * 32.0 / synth.sample_rate;
+        for pan in pans(synth) {
+            assert!((pan - expected).abs() <= tolerance, "{}: {} against {}", context, pan, expected);
+        }
+    }
+
+    let mut block = vec![0.0; 250 * CHANNELS];
+    synth.note_on(60, 100);
+    for _ in 0..3 {
+        synth.process(&mut block);
+    }
+    synth.note_on(64, 100);
+    for _ in 0..3 {
+        synth.process(&mut block);
+    }
+    assert_eq!(pans(&synth).len(), 2);
+    assert_following_clock(&synth, "steady");
+
+    // Rate and tempo changes move the shared clock, so the voices never drift from it
+    settings.rate = LfoRate::Beats(0.5);
+    synth.set_lfo(0, settings);
+    for tempo in [90.0, 150.0, 133.0] {
+        synth.set_tempo(tempo);
+        for _ in 0..20 {
+            synth.process(&mut block);
+            assert_following_clock(&synth, &format!("{} bpm", tempo));
+        }
+    }
+
+    // Retriggered per voice, the later note starts its own cycle
+    settings.rate = LfoRate::Hertz(3.0);
+    settings.retrigger = LfoRetrigger::PerVoice;
+    synth.set_lfo(0, settings);
+    synth.note_on(67, 100);
+    synth.process(&mut block);
+    let newest = synth.voices.voices().iter().find(|voice| voice.note == 67).unwrap();
+    assert!((newest.lfos[0].phase - 3.0 * 256.0 / 48000.0).abs() < 1e-3);
+}
+
+#[test]
+fn tremolo_and_auto_pan_do_not_zipper() {
+    // Biggest jump between neighbouring samples of `channel`, skipping the edges of the
+    // slow naive square being played, and that channel's peak
+    fn steps(destination: LfoDestination, depth: f32, channel: usize) -> (f32, f32) {
+        let mut synth = synth();
+        synth.set_waveform(WaveForm::Square);
+        synth.set_quality(Quality::Naive);
+        synth.num_oscillators = 1;
+        synth.set_lfo(0, LfoSettings { rate: LfoRate::Hertz(60.0), ..lfo(WaveForm::Square, destination, depth) });
+        synth.note_on(12, 127);
+
+        let mut block = vec![0.0; 4800 * CHANNELS];
+        synth.process(&mut block);
+        let samples: Vec<f32> = block.iter().skip(channel).step_by(CHANNELS).skip(1000).copied().collect();
+        let peak = samples.iter().fold(0.0, |peak: f32, sample| peak.max(sample.abs()));
+        let step = samples.windows(2)
+            .filter(|pair| pair[0].signum() == pair[1].signum())
+            .fold(0.0, |step: f32, pair| step.max((pair[1] - pair[0]).abs()));
+        (step, peak)
+    }
+
+    // A square LFO at full depth flips the level or the side every half cycle
+    for (destination, channel) in [(LfoDestination::Amplitude, 0), (LfoDestination::Pan, 0), (LfoDestination::Pan, 1)] {
+        let (step, peak) = steps(destination, 1.0, channel);
+        assert!(peak > 0.05, "{:?}", destination);
+        assert!(step < peak / 8.0, "{:?} channel {}: step of {} against a peak of {}", destination, channel, step, peak);
+    }
+}
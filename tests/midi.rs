@@ -0,0 +1,198 @@
+use pulsar::midi::{MidiFile, MidiMessage, MidiParser, MidiReader, MidiRecorder};
+use pulsar::render;
+use pulsar::synth::waveform::WaveForm;
+use pulsar::synth::CHANNELS;
+use pulsar::{Command, Synth, ADSR};
+
+fn parse(bytes: &[u8]) -> Vec<MidiMessage> {
+    let mut messages = Vec::new();
+    MidiParser::new().feed(bytes, |message| messages.push(message));
+    messages
+}
+
+fn drive(synth: &mut Synth, bytes: &[u8]) {
+    for message in parse(bytes) {
+        if let Some(command) = message.to_command() {
+            synth.apply(command);
+        }
+    }
+}
+
+#[test]
+fn parses_channel_messages_with_running_status() {
+    // Note on, two more notes under running status, then a note on with velocity 0
+    let messages = parse(&[0x91, 60, 100, 64, 90, 67, 80, 60, 0]);
+    assert_eq!(messages, vec![
+        MidiMessage::NoteOn { channel: 1, note: 60, velocity: 100 },
+        MidiMessage::NoteOn { channel: 1, note: 64, velocity: 90 },
+        MidiMessage::NoteOn { channel: 1, note: 67, velocity: 80 },
+        MidiMessage::NoteOff { channel: 1, note: 60, velocity: 64 },
+    ]);
+
+    let messages = parse(&[0xE0, 0x00, 0x40, 0xE0, 0x7F, 0x7F, 0xE0, 0x00, 0x00, 0xB0, 64, 127, 0xC3, 5, 0xD3, 40]);
+    assert_eq!(messages, vec![
+        MidiMessage::PitchBend { channel: 0, value: 0 },
+        MidiMessage::PitchBend { channel: 0, value: 8191 },
+        MidiMessage::PitchBend { channel: 0, value: -8192 },
+        MidiMessage::ControlChange { channel: 0, controller: 64, value: 127 },
+        MidiMessage::ProgramChange { channel: 3, program: 5 },
+        MidiMessage::ChannelPressure { channel: 3, pressure: 40 },
+    ]);
+}
+
+#[test]
+fn skips_realtime_sysex_and_stray_data() {
+    let messages = parse(&[
+        // Data before any status byte is dropped
+        60, 100,
+        // A clock tick in the middle of a note on
+        0x90, 60, 0xF8, 100,
+        // System exclusive, including bytes that look like data
+        0xF0, 0x7E, 0x00, 0x09, 0x01, 0xF7,
+        // Song position cancels running status, so these data bytes are ignored
+        0xF2, 0x10, 0x20, 62, 100,
+        0x80, 60, 0,
+    ]);
+    assert_eq!(messages, vec![
+        MidiMessage::NoteOn { channel: 0, note: 60, velocity: 100 },
+        MidiMessage::NoteOff { channel: 0, note: 60, velocity: 0 },
+    ]);
+}
+
+#[test]
+fn round_trips_through_bytes() {
+    let messages = [
+        MidiMessage::NoteOn { channel: 15, note: 127, velocity: 1 },
+        MidiMessage::ControlChange { channel: 2, controller: 1, value: 64 },
+        MidiMessage::PitchBend { channel: 9, value: -1234 },
+        MidiMessage::ProgramChange { channel: 4, program: 12 },
+    ];
+    let bytes: Vec<u8> = messages.iter()
+        .flat_map(|message| {
+            let (bytes, len) = message.to_bytes();
+            bytes[..len].to_vec()
+        })
+        .collect();
+    assert_eq!(parse(&bytes), messages);
+}
+
+#[test]
+fn reader_pulls_messages_from_a_byte_stream() {
+    let recorded: &[u8] = &[0x90, 60, 100, 0xFE, 0x80, 60, 0];
+    let messages: Vec<MidiMessage> = MidiReader::new(recorded).map(Result::unwrap).collect();
+    assert_eq!(messages.len(), 2);
+}
+
+#[test]
+fn drives_the_synth() {
+    let mut synth = Synth::new(48000.0, ADSR::new(1, 10, 0.8, 10));
+    let mut block = vec![0.0; 256 * CHANNELS];
+
+    drive(&mut synth, &[0x90, 60, 100, 64, 100]);
+    assert_eq!(synth.active_voice_count(), 2);
+
+    // Sustain pedal down, then both keys released: the notes keep sounding
+    drive(&mut synth, &[0xB0, 64, 127, 0x80, 60, 0, 64, 0]);
+    assert!(synth.sustain);
+    for _ in 0..20 {
+        synth.process(&mut block);
+    }
+    assert_eq!(synth.active_voice_count(), 2);
+
+    // Pedal up releases them
+    drive(&mut synth, &[0xB0, 64, 0]);
+    for _ in 0..20 {
+        synth.process(&mut block);
+    }
+    assert_eq!(synth.active_voice_count(), 0);
+
+    // Full bend up raises pitch by the bend range
+    drive(&mut synth, &[0xE0, 0x7F, 0x7F, 0xB0, 1, 127, 0xB0, 7, 64]);
+    let bent = synth.get_frequency(69);
+    assert!((bent - 440.0 * 2f32.powf(synth.pitch_bend_range * 8191.0 / 8192.0 / 12.0)).abs() < 0.01);
+    assert_eq!(synth.mod_wheel, 1.0);
+    assert!((synth.master_volume - 64.0 / 127.0).abs() < 1e-6);
+}
+
+fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut bytes = id.to_vec();
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+#[test]
+fn loads_format_1_files_with_a_tempo_map() {
+    // 96 ticks per quarter; the tempo track switches from 120 to 60 bpm after one quarter
+    let mut file = chunk(b"MThd", &[0, 1, 0, 2, 0, 96]);
+    file.extend(chunk(b"MTrk", &[
+        0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20,
+        0x60, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40,
+        0x00, 0xFF, 0x2F, 0x00,
+    ]));
+    file.extend(chunk(b"MTrk", &[
+        0x00, 0xFF, 0x03, 0x04, b'l', b'e', b'a', b'd',
+        // Note on at 0, running status note off (velocity 0) at 96, note on at 192
+        0x00, 0x90, 60, 100,
+        0x60, 60, 0,
+        0x60, 64, 100,
+        0x83, 0x00, 0x80, 64, 0,
+        0x00, 0xFF, 0x2F, 0x00,
+    ]));
+
+    let midi = MidiFile::parse(&file).unwrap();
+    assert_eq!(midi.format, 1);
+    assert_eq!(midi.tracks.len(), 2);
+
+    let events = midi.timed_events();
+    let times: Vec<f64> = events.iter().map(|event| event.time).collect();
+    // One quarter at 120 bpm, then a second per quarter at 60 bpm
+    assert_eq!(times, vec![0.0, 0.5, 1.5, 5.5]);
+    assert_eq!(events[1].command, Command::NoteOff { note: 60 });
+
+    let mut synth = Synth::new(8000.0, ADSR::new(1, 10, 0.8, 10));
+    let samples = render::render(&mut synth, &events);
+    assert!(samples.len() >= (5.5 * 8000.0) as usize * CHANNELS);
+}
+
+#[test]
+fn rejects_malformed_files() {
+    assert!(MidiFile::parse(b"RIFF").is_err());
+    let mut format_2 = chunk(b"MThd", &[0, 2, 0, 1, 0, 96]);
+    format_2.extend(chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]));
+    assert!(MidiFile::parse(&format_2).is_err());
+    let mut truncated = chunk(b"MThd", &[0, 0, 0, 1, 0, 96]);
+    truncated.extend(chunk(b"MTrk", &[0x00, 0x90, 60]));
+    assert!(MidiFile::parse(&truncated).is_err());
+}
+
+#[test]
+fn recordings_round_trip_through_a_file() {
+    let mut recorder = MidiRecorder::new();
+    recorder.record_command(0.0, &Command::NoteOn { note: 60, velocity: 100 });
+    recorder.record_command(0.25, &Command::SetDetune(0.5));
+    recorder.record_command(0.5, &Command::NoteOff { note: 60 });
+    recorder.record_command(0.5, &Command::SetWaveform(WaveForm::Triangle));
+    recorder.record_command(0.75, &Command::NoteOn { note: 64, velocity: 90 });
+    // Quality has no MIDI equivalent
+    assert!(!recorder.record_command(0.8, &Command::ToggleQuality));
+    assert_eq!(recorder.len(), 5);
+
+    // The note still held when the recording stops is released at the end
+    let bytes = recorder.finish(1.5).to_bytes();
+    let midi = MidiFile::parse(&bytes).unwrap();
+    assert_eq!(midi.format, 0);
+
+    let events = midi.timed_events();
+    let times: Vec<f64> = events.iter().map(|event| event.time).collect();
+    assert_eq!(times, vec![0.0, 0.25, 0.5, 0.5, 0.75, 1.5]);
+    let commands: Vec<Command> = events.iter().map(|event| event.command).collect();
+    assert_eq!(commands, vec![
+        Command::NoteOn { note: 60, velocity: 100 },
+        Command::ControlChange { controller: 94, value: 64 },
+        Command::NoteOff { note: 60 },
+        Command::SetWaveform(WaveForm::Triangle),
+        Command::NoteOn { note: 64, velocity: 90 },
+        Command::NoteOff { note: 64 },
+    ]);
+}
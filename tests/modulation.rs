@@ -0,0 +1,124 @@
+use pulsar::synth::lfo::{LfoDestination, LfoRate, LfoSettings};
+use pulsar::synth::patch::{ModDestination, ModSlot, ModSource, ModSources, Patch};
+use pulsar::synth::voice::Voice;
+use pulsar::synth::CHANNELS;
+use pulsar::{Quality, Synth, WaveForm, ADSR};
+use std::time::Duration;
+
+fn synth() -> Synth {
+    let mut synth = Synth::new(48000.0, ADSR::new(10, 10, 1.0, 10));
+    synth.num_oscillators = 3;
+    synth
+}
+
+fn slot(source: ModSource, destination: ModDestination, amount: f32) -> Option<ModSlot> {
+    Some(ModSlot { source, destination, amount })
+}
+
+fn play(synth: &mut Synth, note: u8, velocity: u8) -> &Voice {
+    synth.note_on(note, velocity);
+    let mut block = vec![0.0; 64 * CHANNELS];
+    synth.process(&mut block);
+    synth.voices.voices().iter().rev().find(|voice| !voice.is_free() && voice.note == note).unwrap()
+}
+
+#[test]
+fn slots_sum_into_destinations_in_their_own_units() {
+    let mut patch = Patch::default();
+    patch.lfos[0] = LfoSettings { destination: LfoDestination::Pitch, depth: 0.5, ..LfoSettings::default() };
+    patch.modulation[0] = slot(ModSource::ModWheel, ModDestination::Pitch, 0.5);
+    patch.modulation[3] = slot(ModSource::Lfo(0), ModDestination::Cutoff, -0.2);
+    patch.modulation[7] = slot(ModSource::Velocity, ModDestination::Level, 1.0);
+
+    let sources = ModSources { lfos: [1.0, 0.0], velocity: 0.5, mod_wheel: 1.0, ..ModSources::default() };
+    let targets = patch.evaluate(&sources);
+    // The LFO's own route plus half of the wheel's 12 semitone range
+    assert_eq!(targets.get(ModDestination::Pitch), 0.5 + 6.0);
+    assert_eq!(targets.get(ModDestination::Cutoff), -1.0);
+    assert_eq!(targets.get(ModDestination::Level), 0.5);
+    assert_eq!(targets.get(ModDestination::Pan), 0.0);
+}
+
+#[test]
+fn note_sources_shape_each_voice() {
+    let mut synth = synth();
+    synth.set_mod_slot(0, slot(ModSource::Key, ModDestination::Pitch, 1.0 / 12.0));
+    synth.set_mod_slot(1, slot(ModSource::Velocity, ModDestination::Attack, 1.0 / 3.0));
+
+    let high = play(&mut synth, 127, 127);
+    assert!((high.oscillators.pitch_ratio - 2f32.powf(1.0 / 12.0)).abs() < 1e-5);
+    assert_eq!(high.envelope.adsr.attack, Duration::from_millis(20));
+
+    let soft = play(&mut synth, 60, 0);
+    assert_eq!(soft.envelope.adsr.attack, Duration::from_millis(10));
+
+    // Random is drawn per note
+    synth.set_mod_slot(0, slot(ModSource::Random, ModDestination::Pan, 1.0));
+    synth.set_mod_slot(1, None);
+    let randoms: Vec<f32> = (0..4).map(|note| play(&mut synth, 40 + note, 100).random).collect();
+    assert!(randoms.iter().all(|random| (-1.0..1.0).contains(random)));
+    assert!(randoms.windows(2).any(|pair| pair[0] != pair[1]));
+}
+
+#[test]
+fn mod_wheel_moves_sounding_notes() {
+    let mut synth = synth();
+    synth.set_detune(0.5);
+    synth.set_mod_slot(0, slot(ModSource::ModWheel, ModDestination::Detune, 0.5));
+    synth.set_mod_slot(1, slot(ModSource::ModWheel, ModDestination::Pitch, -1.0));
+
+    let ratios = play(&mut synth, 60, 100).oscillators.lane_ratios;
+    assert_eq!(ratios, [1.0; 8]);
+
+    synth.set_mod_wheel(1.0);
+    let mut block = vec![0.0; 64 * CHANNELS];
+    synth.process(&mut block);
+    let voice = synth.voices.voices().iter().find(|voice| !voice.is_free()).unwrap();
+    assert!((voice.oscillators.pitch_ratio - 0.5).abs() < 1e-6);
+    // The spread widens from 0.5 to 1.0: the outer oscillators move apart, the middle one stays
+    let [low, middle, high, ..] = voice.oscillators.lane_ratios;
+    assert!(low < 1.0 && high > 1.0);
+    assert!((middle - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn velocity_can_turn_a_voice_down() {
+    let mut synth = synth();
+    synth.set_mod_slot(2, slot(ModSource::Velocity, ModDestination::Level, -1.0));
+    synth.note_on(69, 127);
+    let mut block = vec![0.0; 1024 * CHANNELS];
+    synth.process(&mut block);
+    assert!(block.iter().all(|sample| sample.abs() < 1e-6));
+}
+
+// Largest change between neighbouring samples of one channel, against the channel's
+// peak. The oscillator is a slow naive square, so any other movement comes from gain.
+fn largest_step(synth: &mut Synth, channel: usize) -> (f32, f32) {
+    synth.set_waveform(WaveForm::Square);
+    synth.set_quality(Quality::Naive);
+    synth.num_oscillators = 1;
+    synth.note_on(12, 127);
+
+    let mut block = vec![0.0; 4800 * CHANNELS];
+    synth.process(&mut block);
+    let samples: Vec<f32> = block.iter().skip(channel).step_by(CHANNELS).skip(1000).copied().collect();
+    let peak = samples.iter().fold(0.0, |peak: f32, sample| peak.max(sample.abs()));
+    let step = samples.windows(2)
+        .filter(|pair| pair[0].signum() == pair[1].signum())
+        .fold(0.0, |step: f32, pair| step.max((pair[1] - pair[0]).abs()));
+    (step, peak)
+}
+
+#[test]
+fn fast_level_modulation_ramps_between_control_updates() {
+    let mut synth = synth();
+    let square = LfoSettings { shape: WaveForm::Square, rate: LfoRate::Hertz(60.0), ..LfoSettings::default() };
+    synth.set_lfo(0, square);
+    synth.set_mod_slot(0, slot(ModSource::Lfo(0), ModDestination::Level, 1.0));
+
+    // The LFO swings the gain between 0 and 2 in one step; spread over a control
+    // interval that is a small fraction of the peak per sample
+    let (step, peak) = largest_step(&mut synth, 0);
+    assert!(peak > 0.1);
+    assert!(step < peak / 8.0, "step of {} against a peak of {}", step, peak);
+}
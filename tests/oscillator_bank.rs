@@ -0,0 +1,126 @@
+use pulsar::synth::oscillator_bank::OscillatorBank;
+use pulsar::synth::CHANNELS;
+use pulsar::{Quality, Synth, WaveForm, ADSR};
+use std::f32::consts::TAU;
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+fn bank(waveform: WaveForm, quality: Quality, frequencies: &[f32]) -> OscillatorBank {
+    let mut bank = OscillatorBank::new(waveform, quality, SAMPLE_RATE);
+    bank.reset(frequencies, waveform, quality);
+    bank
+}
+
+#[test]
+fn phases_wrap_even_when_a_step_passes_whole_cycles() {
+    // Two and a half cycles per sample
+    let mut bank = bank(WaveForm::Saw, Quality::Naive, &[2.5 * SAMPLE_RATE, 440.0]);
+    for _ in 0..1000 {
+        let (left, right) = bank.next_frame();
+        assert!(left.is_finite() && right.is_finite());
+        assert!(bank.phases.to_array().iter().all(|phase| (0.0..1.0).contains(phase)), "{:?}", bank.phases);
+    }
+}
+
+#[test]
+fn pitch_ratio_is_limited() {
+    let mut bank = bank(WaveForm::Sine, Quality::BandLimited, &[440.0]);
+    bank.set_pitch_ratio(1.0e9);
+    assert_eq!(bank.pitch_ratio, 256.0);
+    bank.set_pitch_ratio(0.0);
+    assert_eq!(bank.pitch_ratio, 1.0 / 256.0);
+    bank.set_pitch_ratio(2.0);
+    assert_eq!(bank.pitch_ratio, 2.0);
+}
+
+// Frequency of a mono sine from its rising zero crossings, interpolated between samples
+fn measure_frequency(samples: &[f32]) -> f32 {
+    let crossings: Vec<f32> = samples.windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+        .map(|(index, pair)| index as f32 + pair[0] / (pair[0] - pair[1]))
+        .collect();
+    let (first, last) = (crossings[0], crossings[crossings.len() - 1]);
+    (crossings.len() - 1) as f32 * SAMPLE_RATE / (last - first)
+}
+
+#[test]
+fn pitch_holds_after_hours_of_playing() {
+    let mut synth = Synth::new(SAMPLE_RATE, ADSR::new(1, 1, 1.0, 1));
+    synth.num_oscillators = 1;
+    // Three hours in, where a clock held as f32 no longer resolves single samples
+    synth.sample_clock = 3 * 3600 * SAMPLE_RATE as u64;
+    synth.note_on(69, 127);
+
+    let mut block = vec![0.0; SAMPLE_RATE as usize * CHANNELS];
+    synth.process(&mut block);
+    let left: Vec<f32> = block.iter().step_by(CHANNELS).copied().collect();
+    let frequency = measure_frequency(&left[1000..]);
+    assert!((frequency - 440.0).abs() < 0.05, "{} Hz", frequency);
+}
+
+#[test]
+fn changing_detune_keeps_the_phase() {
+    let mut synth = Synth::new(SAMPLE_RATE, ADSR::new(1, 1, 1.0, 1));
+    synth.num_oscillators = 3;
+    synth.note_on(57, 127);
+    let mut block = vec![0.0; 1000 * CHANNELS];
+    synth.process(&mut block);
+
+    let voice = |synth: &Synth| synth.voices.voices().iter().find(|voice| !voice.is_free()).unwrap().oscillators;
+    let before = voice(&synth);
+    synth.set_detune(1.0);
+    let after = voice(&synth);
+    assert_ne!(after.frequencies, before.frequencies);
+    assert_eq!(after.phases.to_array(), before.phases.to_array());
+
+    // So the waveform carries straight on rather than jumping
+    let last = block[block.len() - CHANNELS];
+    let mut next = [0.0; CHANNELS];
+    synth.process(&mut next);
+    let step = TAU * 220.0 / SAMPLE_RATE;
+    assert!((next[0] - last).abs() < step, "{} -> {}", last, next[0]);
+}
+
+// Share of the signal's energy that lies off the harmonics of `frequency`, i.e. aliasing.
+// One tenth of a second is analysed, so with `frequency` a multiple of 10 Hz every
+// harmonic and every alias lands exactly on a DFT bin.
+fn alias_ratio(waveform: WaveForm, quality: Quality, frequency: f32) -> f32 {
+    let length = SAMPLE_RATE as usize / 10;
+    let mut bank = bank(waveform, quality, &[frequency]);
+    let samples: Vec<f32> = (0..length).map(|_| bank.next_frame().0).collect();
+
+    let twiddles: Vec<(f32, f32)> = (0..length)
+        .map(|index| (TAU * index as f32 / length as f32).sin_cos())
+        .collect();
+    let harmonic_bin = (frequency / 10.0) as usize;
+    let (mut harmonics, mut aliases) = (0.0, 0.0);
+    for bin in 1..length / 2 {
+        let (mut re, mut im) = (0.0, 0.0);
+        for (index, sample) in samples.iter().enumerate() {
+            let (sin, cos) = twiddles[bin * index % length];
+            re += sample * cos;
+            im -= sample * sin;
+        }
+        let energy = re * re + im * im;
+        if bin % harmonic_bin == 0 {
+            harmonics += energy;
+        } else {
+            aliases += energy;
+        }
+    }
+    aliases / (harmonics + aliases)
+}
+
+#[test]
+fn band_limited_waveforms_alias_less_at_high_notes() {
+    // Near the top of the keyboard, and not a divisor of the sample rate so aliases
+    // fall between the harmonics
+    let frequency = 2490.0;
+    // The triangle's harmonics fall off fast enough that it starts with far less aliasing
+    for (waveform, improvement) in [(WaveForm::Saw, 10.0), (WaveForm::Square, 10.0), (WaveForm::Pulse, 10.0), (WaveForm::Triangle, 1.5)] {
+        let naive = alias_ratio(waveform, Quality::Naive, frequency);
+        let band_limited = alias_ratio(waveform, Quality::BandLimited, frequency);
+        assert!(band_limited * improvement < naive, "{:?}: {} vs naive {}", waveform, band_limited, naive);
+    }
+}
@@ -0,0 +1,153 @@
+use pulsar::alloc_guard::{AllocGuard, RealtimeScope};
+use pulsar::synth::filter::{FilterMode, FilterModel};
+use pulsar::synth::lfo::{LfoDestination, LfoRate, LfoRetrigger, LfoSettings};
+use pulsar::synth::patch::{ModDestination, ModSlot, ModSource};
+use pulsar::synth::{audio_tap, CHANNELS};
+use pulsar::{StealPolicy, Synth, SynthSource, WaveForm, ADSR};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[global_allocator]
+static ALLOCATOR: AllocGuard = AllocGuard;
+
+fn synth() -> Synth {
+    let mut synth = Synth::new(48000.0, ADSR::new(5, 50, 0.7, 20));
+    synth.set_max_polyphony(4);
+    synth
+}
+
+#[test]
+fn render_path_does_not_allocate() {
+    let (mut source, mut controller) = SynthSource::new(synth(), 48000);
+    let mut block = vec![0.0; 256 * CHANNELS];
+
+    for policy in [StealPolicy::Oldest, StealPolicy::Quietest, StealPolicy::SameNote] {
+        controller.set_steal_policy(policy);
+        for waveform in [WaveForm::Sine, WaveForm::Saw, WaveForm::Square, WaveForm::Pulse, WaveForm::Triangle, WaveForm::WhiteNoise] {
+            controller.set_waveform(waveform);
+            controller.toggle_quality();
+            // More notes than voices, so stealing and pending notes are exercised too
+            for note in 48..60 {
+                controller.note_on(note, 100);
+                source.process(&mut block);
+            }
+            controller.set_detune(0.4);
+            controller.set_stereo_width(1.0);
+            controller.pitch_bend(0.5);
+            controller.control_change(64, 127);
+            for note in 48..60 {
+                controller.note_off(note);
+            }
+            controller.set_sustain(false);
+            controller.set_filter_enabled(true);
+            controller.set_filter_model(if policy == StealPolicy::Quietest { FilterModel::Ladder } else { FilterModel::StateVariable });
+            controller.set_filter_mode(FilterMode::BandPass);
+            controller.set_filter_cutoff(800.0);
+            controller.set_filter_envelope_amount(2.0);
+            controller.set_tempo(140.0);
+            controller.set_mod_slot(0, Some(ModSlot {
+                source: ModSource::Random,
+                destination: ModDestination::Detune,
+                amount: 0.5,
+            }));
+            controller.set_mod_slot(1, Some(ModSlot {
+                source: ModSource::Velocity,
+                destination: ModDestination::Attack,
+                amount: -0.3,
+            }));
+            controller.set_lfo(0, LfoSettings {
+                shape: waveform,
+                rate: LfoRate::Beats(0.5),
+                destination: LfoDestination::Cutoff,
+                depth: 1.0,
+                ..LfoSettings::default()
+            });
+            controller.set_lfo(1, LfoSettings {
+                retrigger: LfoRetrigger::Global,
+                destination: LfoDestination::Pan,
+                depth: 0.5,
+                ..LfoSettings::default()
+            });
+            for _ in 0..40 {
+                source.process(&mut block);
+            }
+        }
+    }
+
+    let rendered: Vec<f32> = source.by_ref().take(4096).collect();
+    assert!(rendered.iter().all(|sample| sample.is_finite()));
+    assert_eq!(controller.snapshot().steal_policy, StealPolicy::SameNote);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "heap allocation")]
+fn guard_catches_allocations() {
+    let _scope = RealtimeScope::enter();
+    let buffer = vec![0.0f32; 64];
+    drop(buffer);
+}
+
+#[test]
+fn stop_fades_out_and_ends_source() {
+    let (mut source, mut controller) = SynthSource::new(synth(), 48000);
+    controller.note_on(60, 127);
+    let playing: Vec<f32> = source.by_ref().take(4800 * CHANNELS).collect();
+    assert!(playing.iter().any(|sample| sample.abs() > 0.01));
+
+    controller.stop();
+    // The fade is 20ms, so the source must end well within a second
+    let tail: Vec<f32> = source.by_ref().take(48000 * CHANNELS).collect();
+    assert!(tail.len() < 48000 * CHANNELS);
+    assert!(source.is_finished());
+    assert_eq!(source.next(), None);
+    assert_eq!(tail[tail.len() - CHANNELS..], [0.0; CHANNELS]);
+}
+
+#[test]
+fn full_queue_drops_parameter_changes_but_not_note_offs() {
+    let (mut source, mut controller) = SynthSource::new(synth(), 48000);
+    controller.note_on(60, 127);
+    while controller.set_detune(0.5) {}
+    assert!(!controller.set_detune(0.5));
+
+    // Nothing drains the queue, so the note off gives up after waiting
+    let start = Instant::now();
+    assert!(!controller.note_off(60));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    // With the audio thread running it waits for room and gets through
+    let audio = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        let _: Vec<f32> = source.by_ref().take(48000 * CHANNELS).collect();
+        source
+    });
+    assert!(controller.note_off(60));
+    let mut source = audio.join().unwrap();
+    let _: Vec<f32> = source.by_ref().take(4800 * CHANNELS).collect();
+    assert_eq!(controller.snapshot().active_voices, 0);
+}
+
+#[test]
+fn tap_copies_rendered_blocks_and_drops_whole_blocks_when_full() {
+    let (mut source, mut controller) = SynthSource::new(synth(), 48000);
+    let (tap, mut reader) = audio_tap(3 * 256 * CHANNELS);
+    source.set_tap(tap);
+    let mut block = vec![0.0; 256 * CHANNELS];
+
+    // Nothing is copied until the reader starts the tap
+    source.process(&mut block);
+    assert!(reader.read().is_none());
+
+    reader.start();
+    controller.note_on(60, 127);
+    source.process(&mut block);
+    let tapped: Vec<f32> = std::iter::from_fn(|| reader.read()).collect();
+    assert_eq!(tapped, block);
+
+    for _ in 0..4 {
+        source.process(&mut block);
+    }
+    assert_eq!(reader.dropped(), block.len() as u64);
+    assert_eq!(std::iter::from_fn(|| reader.read()).count(), 3 * block.len());
+}
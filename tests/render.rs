@@ -0,0 +1,104 @@
+use pulsar::render::{parse_events, render, write_wav, SampleFormat};
+use pulsar::synth::CHANNELS;
+use pulsar::{Synth, ADSR};
+use std::fs;
+use std::process;
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+const EVENTS: &str = "
+# A chord with a waveform change and a detune sweep part way through
+0.0     on 60 100
+0.0     on 64 90
+0.05    detune 0.4
+0.1     waveform saw
+0.15    on 67
+0.2     waveform noise
+0.25    off 60
+0.3     off 64
+0.3     off 67
+";
+
+fn synth() -> Synth {
+    Synth::new(SAMPLE_RATE, ADSR::new(10, 50, 0.7, 80))
+}
+
+#[test]
+fn rendering_is_deterministic() {
+    let events = parse_events(EVENTS).unwrap();
+    let first = render(&mut synth(), &events);
+    let second = render(&mut synth(), &events);
+
+    assert!(first.iter().any(|sample| *sample != 0.0));
+    assert_eq!(first.len(), second.len());
+    // Compared bit for bit so even a stray rounding difference shows up
+    let differs = first.iter().zip(&second).position(|(a, b)| a.to_bits() != b.to_bits());
+    assert_eq!(differs, None);
+}
+
+#[test]
+fn events_are_sorted_by_time() {
+    let events = parse_events("1.0 off 60\n0.0 on 60\n").unwrap();
+    assert_eq!(events.iter().map(|event| event.time).collect::<Vec<_>>(), [0.0, 1.0]);
+}
+
+#[test]
+fn malformed_lines_report_their_line_number() {
+    let cases = [
+        ("0.0 on 60\n\n# comment\n0.5 bend 2\n", 4, "unknown event `bend`"),
+        ("0.0 on 60\nsoon off 60\n", 2, "invalid number `soon`"),
+        ("0.0 on 128\n", 1, "`128` is not in 0..=127"),
+        ("0.0 on 60\n0.1 off\n", 2, "missing argument"),
+        ("0.0 on 60\n0.1\n", 2, "missing event"),
+        ("-1.0 on 60\n", 1, "event time cannot be negative"),
+        ("0.0 on 60\n0.0 waveform kazoo\n", 2, "unknown waveform `kazoo`"),
+    ];
+
+    for (text, line, message) in cases {
+        let error = parse_events(text).unwrap_err();
+        assert_eq!((error.line, error.message.as_str()), (line, message), "{:?}", text);
+        assert_eq!(error.to_string(), format!("line {}: {}", line, message));
+    }
+}
+
+#[test]
+fn wav_files_round_trip_in_every_format() {
+    let events = parse_events("0.0 on 69 100\n0.05 off 69\n").unwrap();
+    let samples = render(&mut synth(), &events);
+    assert!(samples.iter().any(|sample| sample.abs() > 0.1));
+
+    let formats = [
+        ("16", SampleFormat::Int16, 16, hound::SampleFormat::Int),
+        ("24", SampleFormat::Int24, 24, hound::SampleFormat::Int),
+        ("32f", SampleFormat::Float32, 32, hound::SampleFormat::Float),
+    ];
+    for (name, format, bits, sample_format) in formats {
+        assert_eq!(SampleFormat::parse(name), Some(format));
+
+        let path = std::env::temp_dir().join(format!("pulsar-render-{}-{}.wav", process::id(), name));
+        write_wav(&path, &samples, CHANNELS as u16, SAMPLE_RATE as u32, format).unwrap();
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.bits_per_sample, bits);
+        assert_eq!(spec.sample_format, sample_format);
+        assert_eq!(spec.channels as usize, CHANNELS);
+        assert_eq!(spec.sample_rate, SAMPLE_RATE as u32);
+
+        // Integer formats are within one step of the original, float is exact
+        let read: Vec<f32> = match format {
+            SampleFormat::Int16 => reader.samples::<i16>().map(|sample| sample.unwrap() as f32 / i16::MAX as f32).collect(),
+            SampleFormat::Int24 => reader.samples::<i32>().map(|sample| sample.unwrap() as f32 / 8_388_607.0).collect(),
+            SampleFormat::Float32 => reader.samples::<f32>().map(Result::unwrap).collect(),
+        };
+        let step = match format {
+            SampleFormat::Int16 => 1.0 / i16::MAX as f32,
+            SampleFormat::Int24 => 1.0 / 8_388_607.0,
+            SampleFormat::Float32 => 0.0,
+        };
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.len(), samples.len(), "{}", name);
+        let worst = read.iter().zip(&samples).fold(0.0, |worst: f32, (a, b)| worst.max((a - b).abs()));
+        assert!(worst <= step * 1.01, "{}: off by {}", name, worst);
+    }
+}
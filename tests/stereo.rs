@@ -0,0 +1,87 @@
+use pulsar::synth::CHANNELS;
+use pulsar::{Synth, SynthSource, ADSR};
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+fn synth(oscillators: u32) -> Synth {
+    let mut synth = Synth::new(SAMPLE_RATE, ADSR::new(1, 1, 1.0, 1));
+    synth.num_oscillators = oscillators;
+    synth
+}
+
+// Energy of each channel over a tenth of a second of a held note
+fn channel_energy(synth: &mut Synth) -> (f32, f32) {
+    synth.note_on(60, 127);
+    let mut block = vec![0.0; SAMPLE_RATE as usize / 10 * CHANNELS];
+    synth.process(&mut block);
+    block.chunks_exact(CHANNELS).fold((0.0, 0.0), |(left, right), frame| {
+        (left + frame[0] * frame[0], right + frame[1] * frame[1])
+    })
+}
+
+#[cfg(feature = "rodio")]
+#[test]
+fn source_is_stereo() {
+    use rodio::Source;
+
+    let (source, _controller) = SynthSource::new(synth(3), SAMPLE_RATE as u32);
+    assert_eq!(source.channels(), 2);
+}
+
+#[test]
+fn source_interleaves_left_and_right() {
+    let mut synth = self::synth(1);
+    synth.set_pan(-1.0);
+    let (source, mut controller) = SynthSource::new(synth, SAMPLE_RATE as u32);
+    controller.note_on(60, 127);
+
+    // Hard left, so every other sample is silent
+    let samples: Vec<f32> = source.skip(1024 * CHANNELS).take(4800 * CHANNELS).collect();
+    assert!(samples.iter().step_by(2).any(|sample| sample.abs() > 0.01));
+    assert!(samples.iter().skip(1).step_by(2).all(|sample| sample.abs() < 1e-6));
+}
+
+#[test]
+fn pan_places_the_voice() {
+    let mut synth = self::synth(1);
+    synth.set_pan(-1.0);
+    let (left, right) = channel_energy(&mut synth);
+    assert!(left > 0.0 && right < left * 1e-6, "{} {}", left, right);
+
+    let mut synth = self::synth(1);
+    synth.set_pan(1.0);
+    let (left, right) = channel_energy(&mut synth);
+    assert!(right > 0.0 && left < right * 1e-6, "{} {}", left, right);
+
+    // Centred, both sides get the same equal-power share
+    let mut synth = self::synth(1);
+    synth.set_pan(0.0);
+    let (left, right) = channel_energy(&mut synth);
+    assert!((left - right).abs() < left * 1e-3, "{} {}", left, right);
+}
+
+#[test]
+fn width_spreads_unison_oscillators() {
+    let mut synth = self::synth(3);
+    synth.set_detune(1.0);
+    synth.set_stereo_width(0.0);
+    synth.note_on(60, 127);
+    let mut block = vec![0.0; 4800 * CHANNELS];
+    synth.process(&mut block);
+    // With no width every oscillator sits in the middle, so the channels match
+    assert!(block.chunks_exact(CHANNELS).all(|frame| (frame[0] - frame[1]).abs() < 1e-6));
+
+    // Full width fans them out evenly from hard left to hard right, around the voice's pan
+    synth.set_stereo_width(1.0);
+    let voice = synth.voices.voices().iter().find(|voice| !voice.is_free()).unwrap();
+    assert_eq!(voice.oscillators.pans[..3], [-1.0, 0.0, 1.0]);
+    synth.process(&mut block);
+    assert!(block.chunks_exact(CHANNELS).any(|frame| (frame[0] - frame[1]).abs() > 1e-3));
+
+    // A panned voice keeps its spread, clamped at the edge of the field
+    synth.set_stereo_width(0.5);
+    synth.set_pan(0.5);
+    synth.note_on(64, 127);
+    let voice = synth.voices.voices().iter().find(|voice| !voice.is_free() && voice.note == 64).unwrap();
+    assert_eq!(voice.oscillators.pans[..3], [0.0, 0.5, 1.0]);
+}
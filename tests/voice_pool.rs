@@ -0,0 +1,152 @@
+use pulsar::synth::voice::{Voice, VoiceState};
+use pulsar::synth::{Command, CHANNELS};
+use pulsar::{StealPolicy, Synth, ADSR};
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+fn synth(policy: StealPolicy, polyphony: usize) -> Synth {
+    let mut synth = Synth::new(SAMPLE_RATE, ADSR::new(5, 5, 1.0, 50));
+    synth.set_steal_policy(policy);
+    synth.set_max_polyphony(polyphony);
+    synth
+}
+
+// Renders long enough for every attack and decay to reach the sustain level
+fn settle(synth: &mut Synth) {
+    let mut block = vec![0.0; 1024 * CHANNELS];
+    synth.process(&mut block);
+}
+
+fn voice(synth: &Synth, note: u8) -> &Voice {
+    synth.voices.voices().iter().find(|voice| !voice.is_free() && voice.note == note).unwrap()
+}
+
+// The note a stolen voice will play once its fade completes
+fn stolen_for(synth: &Synth, note: u8) -> Option<u8> {
+    let voice = voice(synth, note);
+    assert_eq!(voice.state, VoiceState::Stealing);
+    voice.pending().map(|pending| pending.note)
+}
+
+#[test]
+fn oldest_steals_the_first_note_started() {
+    let mut synth = synth(StealPolicy::Oldest, 3);
+    for note in [60, 62, 64] {
+        synth.note_on(note, 100);
+        settle(&mut synth);
+    }
+
+    synth.note_on(65, 100);
+    assert_eq!(stolen_for(&synth, 60), Some(65));
+    assert_eq!(voice(&synth, 62).state, VoiceState::Playing);
+    assert_eq!(voice(&synth, 64).state, VoiceState::Playing);
+}
+
+#[test]
+fn quietest_steals_the_lowest_level() {
+    let mut synth = synth(StealPolicy::Quietest, 3);
+    for (note, velocity) in [(60, 100), (62, 20), (64, 80)] {
+        synth.note_on(note, velocity);
+        settle(&mut synth);
+    }
+
+    synth.note_on(65, 100);
+    assert_eq!(stolen_for(&synth, 62), Some(65));
+    assert_eq!(voice(&synth, 60).state, VoiceState::Playing);
+}
+
+#[test]
+fn same_note_reuses_the_sounding_voice() {
+    let mut synth = synth(StealPolicy::SameNote, 8);
+    synth.note_on(60, 100);
+    synth.note_on(62, 100);
+    settle(&mut synth);
+
+    // Replayed while voices are still free, it takes over its old voice anyway
+    synth.note_on(60, 90);
+    assert_eq!(stolen_for(&synth, 60), Some(60));
+    assert_eq!(synth.active_voice_count(), 2);
+
+    // With no matching note it falls back to the oldest once the pool is full
+    let mut synth = self::synth(StealPolicy::SameNote, 2);
+    synth.note_on(60, 100);
+    settle(&mut synth);
+    synth.note_on(62, 100);
+    settle(&mut synth);
+    synth.note_on(64, 100);
+    assert_eq!(stolen_for(&synth, 60), Some(64));
+}
+
+#[test]
+fn polyphony_caps_the_sounding_voices() {
+    let mut synth = synth(StealPolicy::Oldest, 4);
+    for note in 60..70 {
+        synth.note_on(note, 100);
+        assert!(synth.active_voice_count() <= 4);
+        settle(&mut synth);
+        assert!(synth.active_voice_count() <= 4);
+    }
+
+    // Only the last four notes survive the steals
+    let mut notes: Vec<u8> = synth.voices.voices().iter().filter(|voice| voice.held).map(|voice| voice.note).collect();
+    notes.sort();
+    assert_eq!(notes, [66, 67, 68, 69]);
+
+    // Lowering the cap leaves sounding voices alone but applies to the next note
+    synth.set_max_polyphony(2);
+    synth.note_on(70, 100);
+    assert_eq!(synth.active_voice_count(), 4);
+    assert_eq!(stolen_for(&synth, 66), Some(70));
+}
+
+#[test]
+fn stolen_voices_fade_out_before_the_new_note_starts() {
+    let mut synth = synth(StealPolicy::Oldest, 1);
+    synth.note_on(60, 127);
+    settle(&mut synth);
+
+    synth.note_on(72, 127);
+    let mut frame = [0.0; CHANNELS];
+    let mut loudness = voice(&synth, 60).loudness();
+    let mut fade_frames = 0;
+    while synth.voices.voices().iter().any(|voice| voice.state == VoiceState::Stealing) {
+        synth.process(&mut frame);
+        fade_frames += 1;
+        if let Some(voice) = synth.voices.voices().iter().find(|voice| voice.state == VoiceState::Stealing) {
+            assert!(voice.loudness() <= loudness);
+            loudness = voice.loudness();
+        }
+        assert!(fade_frames <= 1000, "the steal fade never finished");
+    }
+
+    // Five milliseconds, give or take the frame the new note starts in
+    let expected = (0.005 * SAMPLE_RATE) as i32;
+    assert!((fade_frames - expected).abs() <= 1, "faded over {} frames", fade_frames);
+    assert_eq!(voice(&synth, 72).state, VoiceState::Playing);
+    assert_eq!(synth.active_voice_count(), 1);
+}
+
+#[test]
+fn all_notes_off_releases_and_panic_silences() {
+    let mut synth = synth(StealPolicy::Oldest, 8);
+    for note in [60, 64, 67] {
+        synth.note_on(note, 100);
+    }
+    settle(&mut synth);
+
+    // Voices ring out through their release instead of cutting off
+    synth.apply(Command::AllNotesOff);
+    assert_eq!(synth.active_voice_count(), 3);
+    assert!(synth.voices.voices().iter().all(|voice| !voice.held));
+    for _ in 0..4 {
+        settle(&mut synth);
+    }
+    assert_eq!(synth.active_voice_count(), 0);
+
+    for note in [60, 64, 67] {
+        synth.note_on(note, 100);
+    }
+    settle(&mut synth);
+    synth.apply(Command::Panic);
+    assert_eq!(synth.active_voice_count(), 0);
+}